@@ -25,7 +25,8 @@ fn criterion_benchmark(c: &mut Criterion) {
                 let mss_kgen = MssPrivateKeyGenerator::<
                     MamSpongos,
                     WotsPrivateKeyGenerator<MamSpongos>,
-                >::from_depth(*depth as usize);
+                >::from_depth(*depth as usize)
+                .unwrap();
                 mss_kgen.generate(&seed_trits, &nonce).unwrap();
             })
         });
@@ -32,7 +32,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| {
             let mut layer = MamSponge::default();
             layer.absorb((SpongeCtrl::Key, TRYTES.trits())).unwrap();
-            layer.squeeze((SpongeCtrl::Prn, 81 * 3));
+            layer.squeeze((SpongeCtrl::Prn, 81 * 3)).unwrap();
         })
     });
 
@@ -40,7 +40,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| {
             let mut layer = MamSponge::default();
             layer.absorb((SpongeCtrl::Key, TRYTES.trits())).unwrap();
-            layer.squeeze((SpongeCtrl::Prn, 81 * 162));
+            layer.squeeze((SpongeCtrl::Prn, 81 * 162)).unwrap();
         })
     });
 
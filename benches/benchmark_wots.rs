@@ -6,7 +6,10 @@ use iota_conversion::Trinary;
 use mam_rs::{
     definitions::ss::{PrivateKey, PrivateKeyGenerator},
     spongos::MamSpongos,
-    wots::{WotsPrivateKey, WotsPrivateKeyGenerator},
+    wots::{
+        WotsPrivateKey, WotsPrivateKeyGenerator, MAM_WOTS_PRIVATE_KEY_SIZE,
+        MAM_WOTS_PUBLIC_KEY_SIZE,
+    },
 };
 
 const SEED: &str =
@@ -44,6 +47,30 @@ fn criterion_benchmark(c: &mut Criterion) {
             sk_.generate_public_key();
         })
     });
+
+    // Test Sign, allocating and into a stack buffer
+    c.bench_function("WOTS_SIGN", |b| {
+        b.iter(|| {
+            sk.sign(&seed_trits).unwrap();
+        })
+    });
+
+    let mut signature = [0_i8; MAM_WOTS_PRIVATE_KEY_SIZE];
+    c.bench_function("WOTS_SIGN_INTO", |b| {
+        b.iter(|| {
+            sk.sign_into(&seed_trits, &mut signature).unwrap();
+        })
+    });
+
+    // Test Recover Public Key
+    let sig = sk.sign(&seed_trits).unwrap();
+    let mut public_key = [0_i8; MAM_WOTS_PUBLIC_KEY_SIZE];
+    c.bench_function("WOTS_RECOVER_INTO", |b| {
+        b.iter(|| {
+            sig.recover_public_key_into(&seed_trits, &mut public_key)
+                .unwrap();
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
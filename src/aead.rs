@@ -0,0 +1,218 @@
+//! AEAD Layer
+//!
+//! Authenticated encryption with associated data over `MamSponge`. The sponge
+//! absorbs the key, the nonce, the associated data and the plain text length,
+//! encrypts the plain text and squeezes a MAC with the `Mac` control trit, so
+//! the tag covers all of them.
+//!
+
+use crate::{
+    constants::{put_int, put_sized, MAM_SIZE_FIELD_LEN},
+    definitions::{ct_eq, Sponge, Trit},
+    sponge::{MamSponge, SpongeCtrl, MAM_SPONGE_KEY_SIZE, MAM_SPONGE_MAC_SIZE},
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// Size of an AEAD key
+pub const MAM_AEAD_KEY_SIZE: usize = MAM_SPONGE_KEY_SIZE;
+/// Size of an AEAD nonce
+pub const MAM_AEAD_NONCE_SIZE: usize = 81;
+/// Size of an AEAD tag
+pub const MAM_AEAD_MAC_SIZE: usize = MAM_SPONGE_MAC_SIZE;
+
+///
+/// AEAD Error
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AeadError {
+    /// Key is not `MAM_AEAD_KEY_SIZE` trits long
+    InvalidKeySize(usize),
+    /// Nonce is not `MAM_AEAD_NONCE_SIZE` trits long
+    InvalidNonceSize(usize),
+    /// Tag is not `MAM_AEAD_MAC_SIZE` trits long
+    InvalidMacSize(usize),
+    /// Tag does not match, the cipher text or the associated data was altered
+    MacMismatch,
+    /// Sponge failure
+    Sponge(String),
+}
+
+impl fmt::Display for AeadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AeadError::InvalidKeySize(len) => write!(
+                f,
+                "AEAD key must be {} trits long, got {}",
+                MAM_AEAD_KEY_SIZE, len
+            ),
+            AeadError::InvalidNonceSize(len) => write!(
+                f,
+                "AEAD nonce must be {} trits long, got {}",
+                MAM_AEAD_NONCE_SIZE, len
+            ),
+            AeadError::InvalidMacSize(len) => write!(
+                f,
+                "AEAD tag must be {} trits long, got {}",
+                MAM_AEAD_MAC_SIZE, len
+            ),
+            AeadError::MacMismatch => write!(f, "AEAD tag mismatch"),
+            AeadError::Sponge(e) => write!(f, "Sponge error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AeadError {}
+
+///
+/// Sponge keyed with `key`, `nonce`, `associated_data` and `text_len`
+///
+/// The associated data and the text are length-prefixed, an empty field would
+/// otherwise absorb like a single zero trit.
+///
+fn keyed_sponge(
+    key: &[Trit],
+    nonce: &[Trit],
+    associated_data: &[Trit],
+    text_len: usize,
+) -> Result<MamSponge, AeadError> {
+    if key.len() != MAM_AEAD_KEY_SIZE {
+        return Err(AeadError::InvalidKeySize(key.len()));
+    }
+    if nonce.len() != MAM_AEAD_NONCE_SIZE {
+        return Err(AeadError::InvalidNonceSize(nonce.len()));
+    }
+
+    let mut header = Vec::with_capacity(2 * MAM_SIZE_FIELD_LEN + associated_data.len());
+    put_sized(&mut header, associated_data);
+    put_int(&mut header, text_len as i64, MAM_SIZE_FIELD_LEN);
+
+    let mut sponge = MamSponge::default();
+    sponge
+        .absorb_trits(SpongeCtrl::Key, key)
+        .and_then(|_| sponge.absorb_trits(SpongeCtrl::Data, nonce))
+        .and_then(|_| sponge.absorb_trits(SpongeCtrl::Data, &header))
+        .map_err(AeadError::Sponge)?;
+    Ok(sponge)
+}
+
+///
+/// Seal
+///
+/// Encrypt `plain_text` and authenticate it together with `associated_data`.
+/// A nonce must never be reused with the same key.
+///
+/// Output: `(cipher_text, mac)`
+///
+pub fn seal(
+    key: &[Trit],
+    nonce: &[Trit],
+    associated_data: &[Trit],
+    plain_text: &[Trit],
+) -> Result<(Vec<Trit>, Vec<Trit>), AeadError> {
+    let mut sponge = keyed_sponge(key, nonce, associated_data, plain_text.len())?;
+    let cipher_text = sponge.encr(plain_text).map_err(AeadError::Sponge)?;
+    let mac = sponge
+        .squeeze((SpongeCtrl::Mac, MAM_AEAD_MAC_SIZE))
+        .map_err(AeadError::Sponge)?;
+    Ok((cipher_text, mac))
+}
+
+///
+/// Open
+///
+/// Decrypt `cipher_text`, the plain text is only released if `mac` matches.
+///
+pub fn open(
+    key: &[Trit],
+    nonce: &[Trit],
+    associated_data: &[Trit],
+    cipher_text: &[Trit],
+    mac: &[Trit],
+) -> Result<Vec<Trit>, AeadError> {
+    if mac.len() != MAM_AEAD_MAC_SIZE {
+        return Err(AeadError::InvalidMacSize(mac.len()));
+    }
+
+    let mut sponge = keyed_sponge(key, nonce, associated_data, cipher_text.len())?;
+    let plain_text = sponge.decr(cipher_text).map_err(AeadError::Sponge)?;
+    let expected = sponge
+        .squeeze((SpongeCtrl::Mac, MAM_AEAD_MAC_SIZE))
+        .map_err(AeadError::Sponge)?;
+
+    if !ct_eq(mac, &expected) {
+        return Err(AeadError::MacMismatch);
+    }
+    Ok(plain_text)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use alloc::vec;
+    use iota_conversion::Trinary;
+
+    const KEY: &str =
+        "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
+
+    #[test]
+    fn seal_and_open() {
+        let key = KEY.trits();
+        let nonce = vec![1; MAM_AEAD_NONCE_SIZE];
+        let ad = "HEADER".trits();
+        let plain_text = "HELLOWORLD".trits();
+
+        let (cipher_text, mac) = seal(&key, &nonce, &ad, &plain_text).unwrap();
+        assert_ne!(cipher_text, plain_text);
+        assert_eq!(mac.len(), MAM_AEAD_MAC_SIZE);
+        assert_eq!(
+            open(&key, &nonce, &ad, &cipher_text, &mac).unwrap(),
+            plain_text
+        );
+
+        let mut altered = cipher_text.clone();
+        altered[0] = if altered[0] == 1 { 0 } else { 1 };
+        assert_eq!(
+            open(&key, &nonce, &ad, &altered, &mac),
+            Err(AeadError::MacMismatch)
+        );
+        assert_eq!(
+            open(&key, &nonce, &"HEADES".trits(), &cipher_text, &mac),
+            Err(AeadError::MacMismatch)
+        );
+        assert_eq!(
+            open(&key, &nonce[1..], &ad, &cipher_text, &mac),
+            Err(AeadError::InvalidNonceSize(80))
+        );
+        assert_eq!(
+            seal(&key[1..], &nonce, &ad, &plain_text),
+            Err(AeadError::InvalidKeySize(242))
+        );
+    }
+
+    #[test]
+    fn separate_empty_fields() {
+        let key = KEY.trits();
+        let nonce = vec![1; MAM_AEAD_NONCE_SIZE];
+
+        let (cipher_text, mac) = seal(&key, &nonce, &[], &[]).unwrap();
+        assert_eq!(open(&key, &nonce, &[], &cipher_text, &mac).unwrap(), vec![]);
+        assert_eq!(
+            open(&key, &nonce, &[0], &cipher_text, &mac),
+            Err(AeadError::MacMismatch)
+        );
+
+        let (cipher_text, mac) = seal(&key, &nonce, &[], &[0]).unwrap();
+        let (empty, empty_mac) = seal(&key, &nonce, &[], &[]).unwrap();
+        assert_ne!(mac, empty_mac);
+        assert_eq!(
+            open(&key, &nonce, &[], &empty, &mac),
+            Err(AeadError::MacMismatch)
+        );
+        assert_eq!(
+            open(&key, &nonce, &[], &cipher_text, &mac).unwrap(),
+            vec![0]
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Async Layer
+//!
+//! Async wrappers for tokio based services. Key generation, signing and
+//! signature verification walk thousands of hash chains, so they are moved to
+//! the blocking thread pool instead of stalling the runtime.
+//!
+
+use crate::{
+    definitions::{
+        ss::{PrivateKey, PrivateKeyGenerator},
+        Sponge, Trit,
+    },
+    message::{message_digest, MessageIdChain, MAM_MSG_DIGEST_SIZE},
+    mss::MssSignature,
+    subscriber::Subscriber,
+};
+use futures::{Stream, StreamExt};
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+use tokio::task::spawn_blocking;
+
+/// Size of the digest signed for every packet
+pub const MAM_PACKET_DIGEST_SIZE: usize = MAM_MSG_DIGEST_SIZE;
+
+///
+/// Generate a private key on the blocking thread pool
+///
+/// Works for `MssPrivateKeyGenerator` as well as `WotsPrivateKeyGenerator`.
+///
+pub async fn generate<G, S>(
+    generator: G,
+    seed: Vec<Trit>,
+    nonce: Vec<Trit>,
+) -> Result<G::PrivateKey, String>
+where
+    G: PrivateKeyGenerator<S, Error = String> + Send + 'static,
+    G::PrivateKey: Send + 'static,
+{
+    spawn_blocking(move || generator.generate(&seed, &nonce))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+///
+/// Async Signer
+///
+/// Shares a private key between tasks and signs on the blocking thread pool.
+/// Signatures are produced one at a time, as every MSS signature consumes a leaf.
+///
+pub struct AsyncSigner<K> {
+    /// Private Key
+    key: Arc<Mutex<K>>,
+}
+
+impl<K> Clone for AsyncSigner<K> {
+    fn clone(&self) -> Self {
+        AsyncSigner {
+            key: Arc::clone(&self.key),
+        }
+    }
+}
+
+impl<K> AsyncSigner<K>
+where
+    K: PrivateKey + Send + 'static,
+    K::PublicKey: Send + 'static,
+    K::Signature: Send + 'static,
+    K::Error: fmt::Display,
+{
+    ///
+    /// Wrap a private key
+    ///
+    pub fn new(key: K) -> Self {
+        AsyncSigner {
+            key: Arc::new(Mutex::new(key)),
+        }
+    }
+
+    ///
+    /// Public Key
+    ///
+    pub async fn public_key(&self) -> Result<K::PublicKey, String> {
+        let key = Arc::clone(&self.key);
+        spawn_blocking(move || {
+            key.lock()
+                .map(|key| key.generate_public_key())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    ///
+    /// Sign
+    ///
+    pub async fn sign(&self, message: Vec<Trit>) -> Result<K::Signature, String> {
+        let key = Arc::clone(&self.key);
+        spawn_blocking(move || {
+            key.lock()
+                .map_err(|e| e.to_string())?
+                .sign(&message)
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+///
+/// Signed Packet
+///
+#[derive(Debug)]
+pub struct SignedPacket<S> {
+    /// Channel Id
+    pub channel_id: Vec<Trit>,
+    /// Endpoint Id
+    pub endpoint_id: Vec<Trit>,
+    /// Message ordinal
+    pub ord: i64,
+    /// Payload
+    pub payload: Vec<Trit>,
+    /// Endpoint signature over the packet digest
+    pub signature: MssSignature<S>,
+}
+
+impl<S> SignedPacket<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    ///
+    /// Digest signed by the endpoint, binding the payload to its message id
+    ///
+    pub fn digest(
+        channel_id: &[Trit],
+        endpoint_id: &[Trit],
+        ord: i64,
+        payload: &[Trit],
+    ) -> Result<Vec<Trit>, String> {
+        message_digest::<S>(channel_id, endpoint_id, ord, payload)
+    }
+}
+
+///
+/// Packet Writer
+///
+/// Signs consecutive payloads of an endpoint.
+///
+pub struct PacketWriter<S, K> {
+    /// Signer
+    signer: AsyncSigner<K>,
+    /// Channel Id
+    channel_id: Vec<Trit>,
+    /// Endpoint Id
+    endpoint_id: Vec<Trit>,
+    /// Message Ids
+    ids: MessageIdChain<S>,
+}
+
+impl<S, K> PacketWriter<S, K>
+where
+    S: Sponge<Error = String> + Default + Send + 'static,
+    K: PrivateKey<Signature = MssSignature<S>> + Send + 'static,
+    K::PublicKey: Send + 'static,
+    K::Error: fmt::Display,
+{
+    ///
+    /// Create a writer for the endpoint `endpoint_id` of `channel_id`
+    ///
+    /// * `ord` - Ordinal of the first message to write
+    ///
+    pub fn new(
+        signer: AsyncSigner<K>,
+        channel_id: &[Trit],
+        endpoint_id: &[Trit],
+        ord: i64,
+    ) -> Self {
+        PacketWriter {
+            signer,
+            channel_id: channel_id.to_vec(),
+            endpoint_id: endpoint_id.to_vec(),
+            ids: MessageIdChain::new(channel_id, endpoint_id, ord),
+        }
+    }
+
+    ///
+    /// Sign `payload` as the next message of the endpoint
+    ///
+    pub async fn write(&mut self, payload: Vec<Trit>) -> Result<SignedPacket<S>, String> {
+        let ord = self.ids.ord();
+        let digest = SignedPacket::<S>::digest(&self.channel_id, &self.endpoint_id, ord, &payload)?;
+        let signature = self.signer.sign(digest).await?;
+        self.ids.next_id()?;
+
+        Ok(SignedPacket {
+            channel_id: self.channel_id.clone(),
+            endpoint_id: self.endpoint_id.clone(),
+            ord,
+            payload,
+            signature,
+        })
+    }
+}
+
+///
+/// Packet Reader
+///
+/// Verifies packets against a shared subscriber registry.
+///
+pub struct PacketReader<S> {
+    /// Subscriber
+    subscriber: Arc<Mutex<Subscriber<S>>>,
+    /// Market Data
+    _sponge: PhantomData<S>,
+}
+
+impl<S> PacketReader<S>
+where
+    S: Sponge<Error = String> + Default + Send + 'static,
+{
+    ///
+    /// Create a reader over a shared subscriber
+    ///
+    pub fn new(subscriber: Arc<Mutex<Subscriber<S>>>) -> Self {
+        PacketReader {
+            subscriber,
+            _sponge: PhantomData,
+        }
+    }
+
+    ///
+    /// Subscriber
+    ///
+    pub fn subscriber(&self) -> Arc<Mutex<Subscriber<S>>> {
+        Arc::clone(&self.subscriber)
+    }
+
+    ///
+    /// Verify a packet and update the subscriber
+    ///
+    pub async fn read(&self, packet: SignedPacket<S>) -> Result<SignedPacket<S>, String> {
+        let subscriber = Arc::clone(&self.subscriber);
+        spawn_blocking(move || {
+            subscriber
+                .lock()
+                .map_err(|e| e.to_string())?
+                .verify_message(
+                    &packet.channel_id,
+                    &packet.endpoint_id,
+                    packet.ord,
+                    &packet.payload,
+                    &packet.signature,
+                )?;
+            Ok(packet)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    ///
+    /// Verify packets coming from an async transport, in order
+    ///
+    pub fn read_stream<'a, P>(
+        &'a self,
+        packets: P,
+    ) -> impl Stream<Item = Result<SignedPacket<S>, String>> + 'a
+    where
+        P: Stream<Item = SignedPacket<S>> + 'a,
+    {
+        packets.then(move |packet| self.read(packet))
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{
+        definitions::ss::PublicKey,
+        mss::{MssPrivateKey, MssPrivateKeyGenerator},
+        spongos::MamSpongos,
+        wots::WotsPrivateKeyGenerator,
+    };
+    use iota_conversion::Trinary;
+
+    type Generator = MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>>;
+    type Mss = MssPrivateKey<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>>;
+
+    const SEED: &str =
+        "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
+
+    #[tokio::test]
+    async fn write_and_read_packets() {
+        let key: Mss = generate(Generator::new(2, 1), SEED.trits(), vec![0; 18])
+            .await
+            .unwrap();
+        let signer = AsyncSigner::new(key);
+        let chid = signer.public_key().await.unwrap().to_bytes().to_vec();
+
+        let mut writer = PacketWriter::new(signer, &chid, &chid, 0);
+        let mut packets = Vec::new();
+        for payload in ["HELLO", "WORLD"].iter() {
+            packets.push(writer.write(payload.trits()).await.unwrap());
+        }
+        packets[1].payload = "WORLE".trits();
+
+        let mut subscriber = Subscriber::default();
+        subscriber.follow(&chid).unwrap();
+        let reader = PacketReader::new(Arc::new(Mutex::new(subscriber)));
+        let results = reader
+            .read_stream(futures::stream::iter(packets))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        let subscriber = reader.subscriber();
+        assert_eq!(
+            subscriber.lock().unwrap().channel(&chid).unwrap().next_ord,
+            1
+        );
+    }
+}
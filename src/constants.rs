@@ -1,26 +1,92 @@
 //! Constants and utilities functions
 //!
-use iota_conversion::Trit;
+use crate::definitions::Trit;
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
 
-/// Utilities functions
+// Utilities functions
+
+/// Encode `value` in balanced ternary, least significant trit first, keeping
+/// exactly `len` trits
 ///
+pub(crate) fn trits_with_length(value: i64, len: usize) -> Vec<Trit> {
+    let mut trits = vec![0; len];
+    let mut rest = value;
+    for trit in trits.iter_mut() {
+        let rem = rest.rem_euclid(3);
+        rest = rest.div_euclid(3);
+        *trit = if rem == 2 {
+            rest += 1;
+            -1
+        } else {
+            rem as Trit
+        };
+    }
+    trits
+}
 
-/// `mn = 3^n`, `mk = 3^k`, `n>k`. t \in [-(M-1)/2 .. (M-1)/2].
+/// Decode a balanced ternary value, least significant trit first
 ///
-pub(crate) fn mam_mods(u: i32, mn: i32, mk: i32) -> i32 {
-    (u + ((mn - 1) / 2) % mk) - (mk - 1) / 2
+pub(crate) fn long_value(trits: &[Trit]) -> i64 {
+    trits
+        .iter()
+        .rev()
+        .fold(0, |acc, trit| acc * 3 + i64::from(*trit))
 }
 
-/// `mn = 3^n`, `mk = 3^k`, `n>k`. t \in [-(M-1)/2 .. (M-1)/2].
+/// Size of the length prefix of a serialized field
+pub(crate) const MAM_SIZE_FIELD_LEN: usize = 27;
+
+/// Append `value` to `out`, encoded in `len` trits
 ///
-pub(crate) fn mam_divs(u: i32, mn: i32, mk: i32) -> i32 {
-    (u + ((mn - 1) / 2) / mk) - (mn / mk - 1) / 2
+pub(crate) fn put_int(out: &mut Vec<Trit>, value: i64, len: usize) {
+    out.extend(trits_with_length(value, len));
 }
 
-/// t[0] + (t[1] * 3 ^ 1) + (t[2] * 3 ^ 3 )
+/// Append `data` to `out`, prefixed with its length
 ///
+pub(crate) fn put_sized(out: &mut Vec<Trit>, data: &[Trit]) {
+    put_int(out, data.len() as i64, MAM_SIZE_FIELD_LEN);
+    out.extend_from_slice(data);
+}
+
+/// Take `len` trits from the front of `input`
 ///
-pub(crate) fn trits_get3(trits: &[i8]) -> Trit {
-    trits[0] + trits[1] * 3 + trits[2] * 9
+pub(crate) fn take<'a>(input: &mut &'a [Trit], len: usize) -> Result<&'a [Trit], String> {
+    if input.len() < len {
+        return Err("Unexpected end of serialized data".to_owned());
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
 }
 
+/// Take an integer encoded in `len` trits from the front of `input`
+///
+pub(crate) fn take_int(input: &mut &[Trit], len: usize) -> Result<i64, String> {
+    take(input, len).map(long_value)
+}
+
+/// Take a length prefixed field from the front of `input`
+///
+pub(crate) fn take_sized(input: &mut &[Trit]) -> Result<Vec<Trit>, String> {
+    let len = take_int(input, MAM_SIZE_FIELD_LEN)?;
+    if len < 0 {
+        return Err("Negative field length in serialized data".to_owned());
+    }
+    take(input, len as usize).map(|field| field.to_vec())
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use iota_conversion::Trinary;
+
+    #[test]
+    fn encode_like_iota_conversion() {
+        for value in -500i64..500 {
+            let trits = trits_with_length(value, 14);
+            assert_eq!(trits, value.trits_with_length(14));
+            assert_eq!(long_value(&trits), value);
+        }
+    }
+}
@@ -1,7 +1,39 @@
 //!
 //! Definitions
 //!
-use iota_conversion::Trit;
+use alloc::{format, string::String, vec::Vec};
+
+/// Trit, a balanced ternary digit in `-1..=1`
+pub type Trit = i8;
+
+///
+/// Check that every trit is in `-1..=1`
+///
+/// Trits decoded from untrusted input should be checked before use, all
+/// layers reject out of range values with this error.
+///
+pub fn check_trits(trits: &[Trit]) -> Result<(), String> {
+    match trits.iter().position(|t| !(-1..=1).contains(t)) {
+        Some(idx) => Err(format!(
+            "Invalid trit {} at position {}, trits must be in -1..=1",
+            trits[idx], idx
+        )),
+        None => Ok(()),
+    }
+}
+
+///
+/// Compare trits in constant time
+///
+/// Slices of different lengths are never equal. Otherwise every trit is
+/// visited, so the time taken does not leak the first mismatch position.
+///
+pub fn ct_eq(a: &[Trit], b: &[Trit]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
 
 /// Mam Sponge Definition
 ///
@@ -22,7 +54,7 @@ where
 
     /// Sponge squeezing
     ///
-    fn squeeze(&mut self, data: Self::SqueezeInput) -> Vec<Trit>;
+    fn squeeze(&mut self, data: Self::SqueezeInput) -> Result<Vec<Trit>, Self::Error>;
 
     /// Sponge Hashing
     ///
@@ -30,10 +62,23 @@ where
     /// * `hash_len` -
     fn hash(&mut self, plain_text: &[Trit], hash_len: usize) -> Result<Vec<Trit>, Self::Error>;
 
+    /// Sponge Hashing into a buffer
+    ///
+    /// * `plain_text` - Input data
+    /// * `hash` - Output buffer, its length is the hash length
+    ///
+    /// The default goes through `hash` and allocates; `MamSponge` and
+    /// `MamSpongos` override it to hash without allocating.
+    fn hash_into(&mut self, plain_text: &[Trit], hash: &mut [Trit]) -> Result<(), Self::Error> {
+        let value = self.hash(plain_text, hash.len())?;
+        hash.copy_from_slice(&value);
+        Ok(())
+    }
+
     /// Sponge AE encryption
     ///
     /// * `plain_text` - Input data
-    fn encr(&mut self, plain_text: &[Trit]) -> Vec<Trit>;
+    fn encr(&mut self, plain_text: &[Trit]) -> Result<Vec<Trit>, Self::Error>;
 
     /// Sponge AE decryption
     ///
@@ -42,7 +87,7 @@ where
     /// Output:
     ///
     /// * `plain_text` - Input data
-    fn decr(&mut self, cipher_text: &[Trit]) -> Vec<Trit>;
+    fn decr(&mut self, cipher_text: &[Trit]) -> Result<Vec<Trit>, Self::Error>;
 
     ///
     /// Reset State
@@ -86,6 +131,7 @@ pub mod ss {
     //!
     //! https://github.com/iotaledger/bee-rfcs/pull/26
     //!
+    use super::Trit;
 
     ///
     /// Trait for Private Key Generator
@@ -97,7 +143,7 @@ pub mod ss {
         type Error;
 
         /// Generate Private Key
-        fn generate(&self, seed: &[i8], nonce: &[i8]) -> Result<Self::PrivateKey, Self::Error>;
+        fn generate(&self, seed: &[Trit], nonce: &[Trit]) -> Result<Self::PrivateKey, Self::Error>;
     }
 
     ///
@@ -108,6 +154,8 @@ pub mod ss {
         type PublicKey;
         /// Signature Type
         type Signature;
+        /// Error
+        type Error;
         ///
         /// Generate Public Key
         ///
@@ -115,47 +163,58 @@ pub mod ss {
         ///
         /// Sign
         ///
-        fn sign(&self, message: &[i8]) -> Result<Self::Signature, String>;
+        fn sign(&self, message: &[Trit]) -> Result<Self::Signature, Self::Error>;
     }
 
     ///
     /// Signature Scheme PublicKey
     ///
-    pub trait PublicKey {
+    pub trait PublicKey: Sized {
         /// Signature Type
         type Signature;
+        /// Error
+        type Error;
+        /// Size of a public key in trits
+        const SIZE: usize;
+
         ///
         /// Verify
         ///
-        fn verify(&self, message: &[i8], signature: &Self::Signature) -> bool;
+        /// The error tells why the signature was rejected.
+        ///
+        fn verify(&self, message: &[Trit], signature: &Self::Signature) -> Result<(), Self::Error>;
         ///
         /// To Bytes
         ///
-        fn to_bytes(&self) -> &[i8];
+        fn to_bytes(&self) -> &[Trit];
         ///
-        /// To Bytes
+        /// From Bytes
         ///
-        fn form_bytes(bytes: &[i8]) -> Self;
+        fn from_bytes(bytes: &[Trit]) -> Result<Self, Self::Error>;
     }
 
     ///
     /// Signature Scheme
     ///
-    pub trait Signature {
+    pub trait Signature: Sized {
         /// PublicKey Type
         type PublicKey;
+        /// Error
+        type Error;
+        /// Largest size of a signature in trits
+        const MAX_SIZE: usize;
 
         ///
         /// Recover Public Key
         ///
-        fn recover_public_key(&self, message: &[i8]) -> Self::PublicKey;
+        fn recover_public_key(&self, message: &[Trit]) -> Result<Self::PublicKey, Self::Error>;
         ///
         /// To Bytes
         ///
-        fn to_bytes(&self) -> &[i8];
+        fn to_bytes(&self) -> &[Trit];
         ///
-        /// To Bytes
+        /// From Bytes
         ///
-        fn form_bytes(bytes: &[i8]) -> Self;
+        fn from_bytes(bytes: &[Trit]) -> Result<Self, Self::Error>;
     }
 }
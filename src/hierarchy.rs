@@ -0,0 +1,174 @@
+//! Key Hierarchy
+//!
+//! Derives every secret of a device from one master seed, so only the master
+//! seed has to be backed up:
+//!
+//! ```text
+//! master seed
+//! ├── channel seed (channel)
+//! │   └── endpoint seed (channel, endpoint)
+//! └── NTRU seed (index)
+//! ```
+//!
+//! A child seed is `Prng::gen(DstSecKey, purpose || index, 243)` keyed with its
+//! parent seed, where the 3-trit purpose separates channels, endpoints and NTRU
+//! keys. Seeds are fed to `MssPrivateKeyGenerator::generate` or used as NTRU
+//! secrets; knowing a child seed reveals nothing about its parent or siblings.
+//!
+
+use crate::{
+    constants::trits_with_length,
+    definitions::Trit,
+    prng::{Prng, PrngDestinationTryte, MAM_PRNG_SECRET_KEY_SIZE},
+};
+use alloc::{string::String, vec::Vec};
+
+/// Size of a derived seed
+pub const MAM_HIERARCHY_SEED_SIZE: usize = MAM_PRNG_SECRET_KEY_SIZE;
+/// Size of the index field of a derivation
+const INDEX_LEN: usize = 27;
+
+/// Key Purpose
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPurpose {
+    /// Channel MSS key
+    Channel = 0,
+    /// Endpoint MSS key
+    Endpoint = 1,
+    /// NTRU key
+    Ntru = 2,
+}
+
+impl KeyPurpose {
+    /// Return Trits
+    pub fn trits(self) -> [Trit; 3] {
+        match self {
+            KeyPurpose::Channel => [0, 0, 0],
+            KeyPurpose::Endpoint => [1, 0, 0],
+            KeyPurpose::Ntru => [-1, 1, 0],
+        }
+    }
+}
+
+///
+/// Key Hierarchy
+///
+#[derive(Clone)]
+pub struct KeyHierarchy {
+    /// Master PRNG
+    prng: Prng,
+}
+
+impl KeyHierarchy {
+    ///
+    /// New hierarchy
+    ///
+    /// * `master_seed` - `MAM_HIERARCHY_SEED_SIZE` trits
+    ///
+    pub fn new(master_seed: &[Trit]) -> Result<Self, String> {
+        Ok(KeyHierarchy {
+            prng: Prng::new(master_seed)?,
+        })
+    }
+
+    ///
+    /// Seed of the channel `channel`
+    ///
+    pub fn channel_seed(&self, channel: u32) -> Result<Vec<Trit>, String> {
+        derive(&self.prng, KeyPurpose::Channel, channel)
+    }
+
+    ///
+    /// Seed of the endpoint `endpoint` of the channel `channel`
+    ///
+    pub fn endpoint_seed(&self, channel: u32, endpoint: u32) -> Result<Vec<Trit>, String> {
+        let channel_prng = Prng::new(&self.channel_seed(channel)?)?;
+        derive(&channel_prng, KeyPurpose::Endpoint, endpoint)
+    }
+
+    ///
+    /// Seed of the NTRU key `index`
+    ///
+    pub fn ntru_seed(&self, index: u32) -> Result<Vec<Trit>, String> {
+        derive(&self.prng, KeyPurpose::Ntru, index)
+    }
+}
+
+///
+/// Child seed of `parent` for `purpose` and `index`
+///
+fn derive(parent: &Prng, purpose: KeyPurpose, index: u32) -> Result<Vec<Trit>, String> {
+    let nonce = [
+        &purpose.trits()[..],
+        &trits_with_length(i64::from(index), INDEX_LEN),
+    ]
+    .concat();
+    parent.clone().gen(
+        PrngDestinationTryte::DstSecKey,
+        &nonce,
+        MAM_HIERARCHY_SEED_SIZE,
+    )
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{
+        definitions::ss::{PrivateKey, PrivateKeyGenerator},
+        mss::MssPrivateKeyGenerator,
+        spongos::MamSpongos,
+        wots::WotsPrivateKeyGenerator,
+    };
+    use iota_conversion::Trinary;
+
+    const SEED: &str =
+        "NOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLM";
+
+    #[test]
+    fn derive_independent_seeds() {
+        let hierarchy = KeyHierarchy::new(&SEED.trits()).unwrap();
+        let seeds = [
+            hierarchy.channel_seed(0).unwrap(),
+            hierarchy.channel_seed(1).unwrap(),
+            hierarchy.endpoint_seed(0, 0).unwrap(),
+            hierarchy.endpoint_seed(0, 1).unwrap(),
+            hierarchy.endpoint_seed(1, 0).unwrap(),
+            hierarchy.ntru_seed(0).unwrap(),
+            hierarchy.ntru_seed(1).unwrap(),
+            SEED.trits(),
+        ];
+        for (i, a) in seeds.iter().enumerate() {
+            assert_eq!(a.len(), MAM_HIERARCHY_SEED_SIZE);
+            for b in seeds[i + 1..].iter() {
+                assert_ne!(a, b);
+            }
+        }
+
+        let again = KeyHierarchy::new(&SEED.trits()).unwrap();
+        assert_eq!(again.endpoint_seed(1, 0).unwrap(), seeds[4]);
+        assert!(KeyHierarchy::new(&SEED.trits()[1..]).is_err());
+    }
+
+    #[test]
+    fn generate_channel_key() {
+        let hierarchy = KeyHierarchy::new(&SEED.trits()).unwrap();
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(1, 1);
+        let generate = |seed: Vec<Trit>| {
+            mss_kg
+                .generate(&seed, &[0; 18])
+                .unwrap()
+                .generate_public_key()
+        };
+
+        assert_eq!(
+            generate(hierarchy.channel_seed(3).unwrap()).state,
+            generate(hierarchy.channel_seed(3).unwrap()).state
+        );
+        assert_ne!(
+            generate(hierarchy.channel_seed(3).unwrap()).state,
+            generate(hierarchy.endpoint_seed(3, 0).unwrap()).state
+        );
+    }
+}
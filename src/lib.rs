@@ -10,15 +10,26 @@
     unstable_features
 )]
 // #![cfg_attr(not(debug_assertions), deny(warnings))]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+pub mod aead;
+#[cfg(feature = "async")]
+pub mod async_api;
 mod constants;
 pub mod definitions;
+pub mod hierarchy;
+pub mod message;
 pub mod mss;
 /// PRNG Layer
 pub mod prng;
+pub mod seed;
 /// Sponge Layer
 pub mod sponge;
 pub mod spongos;
+pub mod subscriber;
+mod troika;
 /// WOTS Layer
 pub mod wots;
 
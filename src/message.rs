@@ -0,0 +1,293 @@
+//! Message Layer
+//!
+//! Message ids and addresses are derived by hashing the channel id, the endpoint id
+//! and the message nonce (an ordinal counter), so subscribers can predict where the
+//! next message of a channel will appear.
+//!
+
+use crate::{
+    constants::trits_with_length,
+    definitions::{Sponge, Trit},
+};
+use alloc::{format, string::String, vec::Vec};
+use core::marker::PhantomData;
+
+/// Size of a channel id
+pub const MAM_CHANNEL_ID_SIZE: usize = 243;
+/// Size of an endpoint id
+pub const MAM_ENDPOINT_ID_SIZE: usize = 243;
+/// Size of a message nonce
+pub const MAM_MSG_NONCE_SIZE: usize = 18;
+/// Largest message ordinal a nonce can encode, `(3^18 - 1) / 2`
+pub const MAM_MSG_ORD_MAX: i64 = 193_710_244;
+/// Size of a message id
+pub const MAM_MSG_ID_SIZE: usize = 81;
+/// Size of a message address
+pub const MAM_MSG_ADDRESS_SIZE: usize = 243;
+/// Size of the digest an endpoint signs for every message
+pub const MAM_MSG_DIGEST_SIZE: usize = 243;
+
+///
+/// Hash `channel_id || endpoint_id || nonce` into `out_len` trits
+///
+fn derive<S>(
+    channel_id: &[Trit],
+    endpoint_id: &[Trit],
+    nonce: &[Trit],
+    out_len: usize,
+) -> Result<Vec<Trit>, String>
+where
+    S: Sponge<Error = String> + Default,
+{
+    if channel_id.len() != MAM_CHANNEL_ID_SIZE {
+        return Err(format!(
+            "Channel id must be {} trits long",
+            MAM_CHANNEL_ID_SIZE
+        ));
+    }
+    if endpoint_id.len() != MAM_ENDPOINT_ID_SIZE {
+        return Err(format!(
+            "Endpoint id must be {} trits long",
+            MAM_ENDPOINT_ID_SIZE
+        ));
+    }
+    if nonce.len() != MAM_MSG_NONCE_SIZE {
+        return Err(format!(
+            "Message nonce must be {} trits long",
+            MAM_MSG_NONCE_SIZE
+        ));
+    }
+
+    let mut sponge = S::default();
+    sponge.hash(&[channel_id, endpoint_id, nonce].concat(), out_len)
+}
+
+///
+/// Derive the id of the message sent with `nonce` on the given channel and endpoint
+///
+/// The message id is the first `MAM_MSG_ID_SIZE` trits of the message address.
+///
+/// * `channel_id` - Channel id (MSS public key of the channel)
+/// * `endpoint_id` - Endpoint id, the channel id itself when the message is not
+///   sent through an endpoint
+/// * `nonce` - Message nonce
+///
+pub fn message_id<S>(
+    channel_id: &[Trit],
+    endpoint_id: &[Trit],
+    nonce: &[Trit],
+) -> Result<Vec<Trit>, String>
+where
+    S: Sponge<Error = String> + Default,
+{
+    derive::<S>(channel_id, endpoint_id, nonce, MAM_MSG_ID_SIZE)
+}
+
+///
+/// Derive the address a reader has to poll for the message sent with `nonce`
+///
+/// * `channel_id` - Channel id (MSS public key of the channel)
+/// * `endpoint_id` - Endpoint id
+/// * `nonce` - Message nonce
+///
+pub fn message_address<S>(
+    channel_id: &[Trit],
+    endpoint_id: &[Trit],
+    nonce: &[Trit],
+) -> Result<Vec<Trit>, String>
+where
+    S: Sponge<Error = String> + Default,
+{
+    derive::<S>(channel_id, endpoint_id, nonce, MAM_MSG_ADDRESS_SIZE)
+}
+
+///
+/// Digest signed by the endpoint, binding the payload to its message id
+///
+/// The message id commits to the ordinal, so a signature cannot be replayed
+/// under another ordinal.
+///
+pub fn message_digest<S>(
+    channel_id: &[Trit],
+    endpoint_id: &[Trit],
+    ord: i64,
+    payload: &[Trit],
+) -> Result<Vec<Trit>, String>
+where
+    S: Sponge<Error = String> + Default,
+{
+    let id = message_id::<S>(channel_id, endpoint_id, &message_nonce(ord)?)?;
+    let mut sponge = S::default();
+    sponge.hash(&[&id[..], payload].concat(), MAM_MSG_DIGEST_SIZE)
+}
+
+///
+/// Encode a message ordinal as a message nonce
+///
+/// Fails for ordinals outside `0..=MAM_MSG_ORD_MAX`, which would wrap onto
+/// the nonce of another message.
+///
+pub fn message_nonce(ord: i64) -> Result<Vec<Trit>, String> {
+    if !(0..=MAM_MSG_ORD_MAX).contains(&ord) {
+        return Err(format!(
+            "Message ordinal must be in 0..={}",
+            MAM_MSG_ORD_MAX
+        ));
+    }
+    Ok(trits_with_length(ord, MAM_MSG_NONCE_SIZE))
+}
+
+///
+/// Message Id Chain
+///
+/// Yields the ids of consecutive messages of an endpoint, starting from a given
+/// ordinal. Writers use it to tag outgoing messages and subscribers to predict
+/// the next ones.
+///
+#[derive(Debug, Clone)]
+pub struct MessageIdChain<S> {
+    /// Channel Id
+    channel_id: Vec<Trit>,
+    /// Endpoint Id
+    endpoint_id: Vec<Trit>,
+    /// Ordinal of the next message
+    ord: i64,
+    /// Market Data
+    _sponge: PhantomData<S>,
+}
+
+impl<S> MessageIdChain<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    ///
+    /// Create a chain starting at the message with ordinal `ord`
+    ///
+    pub fn new(channel_id: &[Trit], endpoint_id: &[Trit], ord: i64) -> Self {
+        MessageIdChain {
+            channel_id: channel_id.to_vec(),
+            endpoint_id: endpoint_id.to_vec(),
+            ord,
+            _sponge: PhantomData,
+        }
+    }
+
+    ///
+    /// Ordinal of the next message
+    ///
+    pub fn ord(&self) -> i64 {
+        self.ord
+    }
+
+    ///
+    /// Id of the next message, without advancing the chain
+    ///
+    pub fn peek_id(&self) -> Result<Vec<Trit>, String> {
+        message_id::<S>(
+            &self.channel_id,
+            &self.endpoint_id,
+            &message_nonce(self.ord)?,
+        )
+    }
+
+    ///
+    /// Address of the next message, without advancing the chain
+    ///
+    pub fn peek_address(&self) -> Result<Vec<Trit>, String> {
+        message_address::<S>(
+            &self.channel_id,
+            &self.endpoint_id,
+            &message_nonce(self.ord)?,
+        )
+    }
+
+    ///
+    /// Id of the next message, advancing the chain
+    ///
+    pub fn next_id(&mut self) -> Result<Vec<Trit>, String> {
+        let id = self.peek_id()?;
+        self.ord += 1;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{sponge::MamSponge, spongos::MamSpongos};
+    use alloc::vec;
+    use iota_conversion::Trinary;
+
+    const CHANNEL_ID: &str =
+        "NOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLM";
+    const ENDPOINT_ID: &str =
+        "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
+
+    #[test]
+    fn derive_distinct_ids_per_nonce() {
+        let chid = CHANNEL_ID.trits();
+        let epid = ENDPOINT_ID.trits();
+
+        let id0 = message_id::<MamSpongos>(&chid, &epid, &message_nonce(0).unwrap()).unwrap();
+        let id1 = message_id::<MamSpongos>(&chid, &epid, &message_nonce(1).unwrap()).unwrap();
+        let id2 = message_id::<MamSpongos>(&epid, &chid, &message_nonce(0).unwrap()).unwrap();
+
+        assert_eq!(id0.len(), MAM_MSG_ID_SIZE);
+        assert_ne!(id0, id1);
+        assert_ne!(id0, id2);
+    }
+
+    #[test]
+    fn message_id_prefixes_address() {
+        let chid = CHANNEL_ID.trits();
+        let epid = ENDPOINT_ID.trits();
+        let nonce = message_nonce(42).unwrap();
+
+        let id = message_id::<MamSponge>(&chid, &epid, &nonce).unwrap();
+        let address = message_address::<MamSponge>(&chid, &epid, &nonce).unwrap();
+
+        assert_eq!(address.len(), MAM_MSG_ADDRESS_SIZE);
+        assert_eq!(id[..], address[..MAM_MSG_ID_SIZE]);
+    }
+
+    #[test]
+    fn predict_next_message_id() {
+        let chid = CHANNEL_ID.trits();
+        let epid = ENDPOINT_ID.trits();
+        let mut writer: MessageIdChain<MamSpongos> = MessageIdChain::new(&chid, &epid, 0);
+        let mut reader: MessageIdChain<MamSpongos> = MessageIdChain::new(&chid, &epid, 0);
+
+        writer.next_id().unwrap();
+        reader.next_id().unwrap();
+        let expected = reader.peek_id().unwrap();
+
+        assert_eq!(writer.next_id().unwrap(), expected);
+        assert_eq!(writer.ord(), 2);
+    }
+
+    #[test]
+    fn reject_bad_lengths() {
+        let chid = CHANNEL_ID.trits();
+        let epid = ENDPOINT_ID.trits();
+
+        assert!(message_id::<MamSpongos>(&chid[1..], &epid, &message_nonce(0).unwrap()).is_err());
+        assert!(message_id::<MamSpongos>(&chid, &epid, &[0; 3]).is_err());
+    }
+
+    #[test]
+    fn reject_ords_out_of_range() {
+        assert_eq!(
+            message_nonce(MAM_MSG_ORD_MAX).unwrap(),
+            vec![1; MAM_MSG_NONCE_SIZE]
+        );
+        assert!(message_nonce(MAM_MSG_ORD_MAX + 1).is_err());
+        assert!(message_nonce(-1).is_err());
+
+        let chid = CHANNEL_ID.trits();
+        let epid = ENDPOINT_ID.trits();
+        let mut chain: MessageIdChain<MamSpongos> =
+            MessageIdChain::new(&chid, &epid, MAM_MSG_ORD_MAX + 1);
+        assert!(chain.next_id().is_err());
+        assert_eq!(chain.ord(), MAM_MSG_ORD_MAX + 1);
+    }
+}
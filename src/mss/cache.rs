@@ -0,0 +1,263 @@
+//!
+//! Authentication Path Cache
+//!
+//! Consecutive leaves of a Merkle tree share the upper part of their
+//! authentication paths. The internal nodes of the last path verified up to
+//! the root are remembered, so the next signature stops hashing as soon as it
+//! reaches one. Older paths are dropped, at most `2 * depth` nodes are kept.
+//!
+use crate::{
+    definitions::{
+        ct_eq,
+        ss::{PublicKey, Signature},
+        Sponge, Trit,
+    },
+    mss::{MssError, MssPublicKey, MssSignature, HASH_LEN},
+    wots::WotsSignature,
+};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+///
+/// Verified internal nodes of one Merkle tree, keyed by level and position
+///
+/// Only the nodes of the last verified path and their siblings are kept.
+///
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NodeCache {
+    /// Depth of the cached tree
+    depth: Option<usize>,
+    /// Nodes, level 0 holds the leaves
+    nodes: BTreeMap<(usize, usize), Vec<Trit>>,
+}
+
+impl NodeCache {
+    ///
+    /// Verify `signature` against `root`
+    ///
+    /// Nodes of a tree with another depth are dropped, as their positions
+    /// do not refer to the same tree.
+    ///
+    pub(crate) fn verify<S>(
+        &mut self,
+        spongos: &mut S,
+        root: &[Trit],
+        message: &[Trit],
+        signature: &MssSignature<S>,
+    ) -> Result<(), MssError>
+    where
+        S: Sponge<Error = String> + Default,
+    {
+        let parsed = signature.parse()?;
+        if self.depth != Some(parsed.depth) {
+            self.nodes.clear();
+            self.depth = Some(parsed.depth);
+        }
+
+        let wots: WotsSignature<S> = WotsSignature::from_bytes(parsed.wots_signature)?;
+        let mut t = wots.recover_public_key(message)?.to_bytes().to_vec();
+        let mut pos = parsed.skn;
+        let mut pending = Vec::new();
+
+        for (level, sibling) in parsed.auth_path.iter().enumerate() {
+            if let Some(cached) = self.nodes.get(&(level, pos)) {
+                return if ct_eq(cached, &t) {
+                    self.keep_path(parsed.skn, pending);
+                    Ok(())
+                } else {
+                    Err(MssError::PublicKeyMismatch)
+                };
+            }
+
+            let pair = if pos % 2 == 0 {
+                [&t, *sibling].concat()
+            } else {
+                [*sibling, &t].concat()
+            };
+            let parent = spongos.hash(&pair, HASH_LEN).map_err(MssError::Sponge)?;
+            // leaves are WOTS public keys, only internal nodes are worth keeping
+            if level > 0 {
+                pending.push(((level, pos ^ 1), sibling.to_vec()));
+                pending.push(((level, pos), t));
+            }
+            t = parent;
+            pos /= 2;
+        }
+
+        if !ct_eq(root, &t) {
+            return Err(MssError::PublicKeyMismatch);
+        }
+        self.keep_path(parsed.skn, pending);
+        Ok(())
+    }
+
+    ///
+    /// Store the `pending` nodes of leaf `skn` and drop the nodes off its path
+    ///
+    /// Nodes above the first cached one were on the previous path as well, so
+    /// the path stays complete.
+    ///
+    fn keep_path(&mut self, skn: usize, pending: Vec<((usize, usize), Vec<Trit>)>) {
+        self.nodes.extend(pending);
+        self.nodes
+            .retain(|&(level, pos), _| pos / 2 == skn >> (level + 1));
+    }
+}
+
+///
+/// Cached MSS Verifier
+///
+/// Verifies consecutive signatures of one MSS public key, reusing the
+/// authentication path nodes verified for previous leaves.
+///
+#[derive(Debug, Clone)]
+pub struct CachedMssVerifier<S> {
+    /// Public Key
+    public_key: MssPublicKey<S>,
+    /// Verified nodes
+    cache: NodeCache,
+}
+
+impl<S> CachedMssVerifier<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    ///
+    /// Wrap a public key
+    ///
+    pub fn new(public_key: MssPublicKey<S>) -> Self {
+        CachedMssVerifier {
+            public_key,
+            cache: NodeCache::default(),
+        }
+    }
+
+    ///
+    /// Public Key
+    ///
+    pub fn public_key(&self) -> &MssPublicKey<S> {
+        &self.public_key
+    }
+
+    ///
+    /// Verify
+    ///
+    /// Same result as `MssPublicKey::verify`, recovery stops at the first
+    /// node already verified.
+    ///
+    pub fn verify(
+        &mut self,
+        message: &[Trit],
+        signature: &MssSignature<S>,
+    ) -> Result<(), MssError> {
+        let mut spongos = S::default();
+        self.cache
+            .verify(&mut spongos, self.public_key.to_bytes(), message, signature)
+    }
+
+    ///
+    /// Number of cached nodes
+    ///
+    pub fn cached_nodes(&self) -> usize {
+        self.cache.nodes.len()
+    }
+
+    ///
+    /// Forget every cached node
+    ///
+    pub fn clear(&mut self) {
+        self.cache = NodeCache::default();
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{
+        definitions::ss::{PrivateKey, PrivateKeyGenerator},
+        mss::MssPrivateKeyGenerator,
+        spongos::MamSpongos,
+        wots::WotsPrivateKeyGenerator,
+    };
+    use iota_conversion::Trinary;
+
+    const SEED: &str =
+        "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
+
+    #[test]
+    fn keep_verified_nodes() {
+        let message = SEED.trits();
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(1, 3);
+        let private_key = mss_kg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let root = private_key.generate_public_key().to_bytes().to_vec();
+        let first = private_key.sign(&message).unwrap();
+        let second = private_key.sign(&message).unwrap();
+
+        let mut spongos = MamSpongos::default();
+        let mut cache = NodeCache::default();
+        assert!(cache.verify(&mut spongos, &root, &message, &first).is_ok());
+        // both children of levels 1 and 2 on the path of leaf 0
+        assert_eq!(cache.nodes.len(), 4);
+        assert!(cache.nodes.contains_key(&(1, 0)));
+        assert!(cache.nodes.contains_key(&(1, 1)));
+
+        assert!(cache.verify(&mut spongos, &root, &message, &second).is_ok());
+        assert_eq!(cache.nodes.len(), 4);
+
+        let mut forged = second.to_bytes().to_vec();
+        forged[20] = if forged[20] == 1 { 0 } else { 1 };
+        let forged = MssSignature::from_bytes(&forged).unwrap();
+        assert_eq!(
+            cache.verify(&mut spongos, &root, &message, &forged),
+            Err(MssError::PublicKeyMismatch)
+        );
+        assert_eq!(cache.nodes.len(), 4);
+    }
+
+    #[test]
+    fn bound_cached_nodes() {
+        let message = SEED.trits();
+        let depth = 4;
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(2, 2);
+        let private_key = mss_kg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let mut verifier = CachedMssVerifier::new(private_key.generate_public_key());
+
+        for _ in 0..1 << depth {
+            let signature = private_key.sign(&message).unwrap();
+            assert!(verifier.verify(&message, &signature).is_ok());
+            assert!(verifier.cached_nodes() <= 2 * depth);
+        }
+    }
+
+    #[test]
+    fn verify_like_public_key() {
+        let message = SEED.trits();
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(1, 3);
+        let private_key = mss_kg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let public_key = private_key.generate_public_key();
+        let mut verifier = CachedMssVerifier::new(public_key.clone());
+        let mut altered = message.clone();
+        altered[0] = if altered[0] == 1 { 0 } else { 1 };
+
+        for _ in 0..(1 << 3) - 1 {
+            let signature = private_key.sign(&message).unwrap();
+            assert!(public_key.verify(&message, &signature).is_ok());
+            assert!(verifier.verify(&message, &signature).is_ok());
+            assert_eq!(
+                verifier.verify(&altered, &signature),
+                Err(MssError::PublicKeyMismatch)
+            );
+            assert_eq!(
+                public_key.verify(&altered, &signature),
+                Err(MssError::PublicKeyMismatch)
+            );
+        }
+        // the internal nodes on the path of the last leaf and their siblings
+        assert_eq!(verifier.cached_nodes(), 4);
+
+        verifier.clear();
+        assert_eq!(verifier.cached_nodes(), 0);
+    }
+}
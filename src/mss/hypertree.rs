@@ -0,0 +1,583 @@
+//!
+//! Hypertree
+//!
+//! Layers of MSS trees in the spirit of XMSS^MT: the leaves of the bottom
+//! trees sign messages, the leaves of every other tree sign the roots of the
+//! trees of the layer below. Only the top root is published; lower trees are
+//! generated from the seed when the previous one is exhausted.
+//!
+//! A hypertree signature is the MSS signature of the bottom tree followed by
+//! the signatures of the roots, up to the one made by the top tree. Roots are
+//! not signed as they are but hashed with `ROOT_DOMAIN` and their layer, so
+//! a root signature never passes for a message signature or for the root
+//! signature of another layer.
+//!
+use crate::{
+    constants::{long_value, trits_with_length},
+    definitions::{
+        check_trits, ct_eq,
+        ss::{PrivateKey, PrivateKeyGenerator, PublicKey, Signature},
+        Sponge, Trit,
+    },
+    mss::{
+        MssError, MssPrivateKey, MssPrivateKeyGenerator, MssPublicKey, MssSignature, HASH_LEN,
+        MAM_MSS_MAX_DEPTH, MAM_MSS_MAX_SIGNATURE_SIZE, MAM_MSS_SIGNATURE_HEADER_SIZE,
+    },
+    wots::MAM_WOTS_SIGNATURE_SIZE,
+};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{cell::RefCell, marker::PhantomData};
+
+/// Largest number of hypertree layers
+pub const MAM_MSS_MAX_LAYERS: usize = 8;
+/// Largest hypertree signature size
+pub const MAM_MSS_MAX_HYPERTREE_SIGNATURE_SIZE: usize =
+    MAM_MSS_MAX_LAYERS * MAM_MSS_MAX_SIGNATURE_SIZE;
+/// Size of the layer in the nonce of a tree
+const LAYER_SIZE: usize = 3;
+/// Size of the tree index in the nonce of a tree
+const TREE_INDEX_SIZE: usize = 27;
+/// Largest number of levels above the bottom trees, their index must fit `TREE_INDEX_SIZE` trits
+const MAX_INDEX_DEPTH: usize = 40;
+/// Domain of the root signatures
+const ROOT_DOMAIN: [Trit; 3] = [1, 1, 1];
+
+///
+/// Nonce of the tree `index` of `layer`
+///
+fn tree_nonce(nonce: &[Trit], layer: usize, index: u64) -> Vec<Trit> {
+    [
+        nonce,
+        &trits_with_length(layer as i64, LAYER_SIZE)[..],
+        &trits_with_length(index as i64, TREE_INDEX_SIZE)[..],
+    ]
+    .concat()
+}
+
+///
+/// Message signed for the root of a tree of `layer` by the layer above
+///
+fn root_message<S>(layer: usize, root: &[Trit]) -> Result<Vec<Trit>, MssError>
+where
+    S: Sponge<Error = String> + Default,
+{
+    S::default()
+        .hash(
+            &[
+                &ROOT_DOMAIN[..],
+                &trits_with_length(layer as i64, LAYER_SIZE)[..],
+                root,
+            ]
+            .concat(),
+            HASH_LEN,
+        )
+        .map_err(MssError::Sponge)
+}
+
+///
+/// MSS Hypertree Generator
+///
+#[derive(Debug)]
+pub struct MssHypertreeGenerator<S, G> {
+    /// Generator of the trees of every layer
+    tree: MssPrivateKeyGenerator<S, G>,
+    /// Layer count
+    layers: usize,
+}
+
+impl<S, G> Clone for MssHypertreeGenerator<S, G> {
+    fn clone(&self) -> Self {
+        MssHypertreeGenerator {
+            tree: self.tree.clone(),
+            layers: self.layers,
+        }
+    }
+}
+
+///
+/// Tree of a layer
+///
+#[derive(Clone)]
+struct Layer<S, G> {
+    /// Private Key
+    key: MssPrivateKey<S, G>,
+    /// Index of the tree in its layer
+    index: u64,
+    /// Signature of the root by the layer above, empty on the top layer
+    root_signature: Vec<Trit>,
+}
+
+///
+/// MSS Hypertree Private Key
+///
+#[derive(Clone)]
+pub struct MssHypertreePrivateKey<S, G> {
+    /// Generator
+    generator: MssHypertreeGenerator<S, G>,
+    /// Seed
+    seed: Vec<Trit>,
+    /// Nonce
+    nonce: Vec<Trit>,
+    /// Current tree of every layer, bottom first
+    layers: RefCell<Vec<Layer<S, G>>>,
+}
+
+///
+/// MSS Hypertree Signature
+///
+#[derive(Debug)]
+pub struct MssHypertreeSignature<S> {
+    /// MSS signatures, bottom first
+    state: Vec<Trit>,
+    /// Market Data
+    _sponge: PhantomData<S>,
+}
+
+impl<S, G> MssHypertreeGenerator<S, G> {
+    ///
+    /// Create MSS Hypertree Generator
+    ///
+    /// Every layer uses trees of `tree`, so the hypertree signs
+    /// _2 ^ (depth x layers)_ messages.
+    ///
+    /// Arguments
+    ///     tree Generator of the trees
+    ///     layers Layer count, in `1..=MAM_MSS_MAX_LAYERS`
+    ///
+    pub fn new(tree: MssPrivateKeyGenerator<S, G>, layers: usize) -> Result<Self, MssError> {
+        let depth: usize = tree.heights.iter().sum();
+        if depth == 0 || depth > MAM_MSS_MAX_DEPTH {
+            return Err(MssError::InvalidDepth(depth as i64));
+        }
+        if layers == 0 || layers > MAM_MSS_MAX_LAYERS || depth * (layers - 1) > MAX_INDEX_DEPTH {
+            return Err(MssError::InvalidLayers(layers));
+        }
+        Ok(MssHypertreeGenerator { tree, layers })
+    }
+
+    ///
+    /// Layer count
+    ///
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    ///
+    /// Generator of the trees
+    ///
+    pub fn tree(&self) -> &MssPrivateKeyGenerator<S, G> {
+        &self.tree
+    }
+}
+
+impl<S, G> PrivateKeyGenerator<S> for MssHypertreeGenerator<S, G>
+where
+    S: Sponge<Error = String> + Default,
+    G: Default + PrivateKeyGenerator<S, Error = String>,
+    <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey + Clone,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Signature: Signature,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Error: Into<MssError>,
+{
+    type PrivateKey = MssHypertreePrivateKey<S, G>;
+    type Error = String;
+
+    ///
+    /// Generate Private Key
+    ///
+    /// Generates the first tree of every layer, each root signed by the
+    /// first tree of the layer above.
+    ///
+    fn generate(&self, seed: &[Trit], nonce: &[Trit]) -> Result<Self::PrivateKey, String> {
+        let top = self.layers - 1;
+        let mut layers = vec![Layer {
+            key: self.tree.generate(seed, &tree_nonce(nonce, top, 0))?,
+            index: 0,
+            root_signature: Vec::new(),
+        }];
+        for layer in (0..top).rev() {
+            let key = self.tree.generate(seed, &tree_nonce(nonce, layer, 0))?;
+            let root_signature = root_message::<S>(layer, &key.root)
+                .and_then(|root| layers[layers.len() - 1].key.sign(&root))
+                .map_err(|e| e.to_string())?;
+            layers.push(Layer {
+                key,
+                index: 0,
+                root_signature: root_signature.state,
+            });
+        }
+        layers.reverse();
+
+        Ok(MssHypertreePrivateKey {
+            generator: self.clone(),
+            seed: seed.to_vec(),
+            nonce: nonce.to_vec(),
+            layers: RefCell::new(layers),
+        })
+    }
+}
+
+impl<S, G> MssHypertreePrivateKey<S, G>
+where
+    S: Sponge<Error = String> + Default,
+    G: Default + PrivateKeyGenerator<S, Error = String>,
+    <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey + Clone,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Signature: Signature,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Error: Into<MssError>,
+{
+    ///
+    /// Number of signatures left
+    ///
+    pub fn sigs_remaining(&self) -> u64 {
+        let layers = self.layers.borrow();
+        let depth = self.generator.tree.depth();
+        let per_tree = 1_u64 << depth;
+        let used = layers[0].index * per_tree + per_tree - layers[0].key.sigs_remaining() as u64;
+        (1_u64 << (depth * self.generator.layers)) - used
+    }
+
+    ///
+    /// Move the bottom layer to its next tree
+    ///
+    /// Every exhausted layer above it moves to its next tree as well. The new
+    /// trees are generated and signed before any of them replaces a current
+    /// one, so a failure leaves the layers as they were. Only the leaf the
+    /// first layer with signatures left may have spent stays used.
+    ///
+    fn next_trees(&self, layers: &mut [Layer<S, G>]) -> Result<(), MssError> {
+        let signer = layers
+            .iter()
+            .position(|layer| layer.key.sigs_remaining() != 0)
+            .ok_or(MssError::KeyExhausted)?;
+
+        let mut keys = Vec::with_capacity(signer);
+        for (layer, current) in layers[..signer].iter().enumerate() {
+            let nonce = tree_nonce(&self.nonce, layer, current.index + 1);
+            keys.push(
+                self.generator
+                    .tree
+                    .generate(&self.seed, &nonce)
+                    .map_err(MssError::KeyGeneration)?,
+            );
+        }
+
+        let mut root_signatures = vec![Vec::new(); signer];
+        for layer in (0..signer).rev() {
+            let root = root_message::<S>(layer, &keys[layer].root)?;
+            let above = match keys.get(layer + 1) {
+                Some(key) => key,
+                None => &layers[signer].key,
+            };
+            root_signatures[layer] = above.sign(&root)?.state;
+        }
+
+        for (layer, (key, root_signature)) in keys.into_iter().zip(root_signatures).enumerate() {
+            layers[layer] = Layer {
+                key,
+                index: layers[layer].index + 1,
+                root_signature,
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<S, G> PrivateKey for MssHypertreePrivateKey<S, G>
+where
+    S: Sponge<Error = String> + Default,
+    G: Default + PrivateKeyGenerator<S, Error = String>,
+    <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey + Clone,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Signature: Signature,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Error: Into<MssError>,
+{
+    type PublicKey = MssPublicKey<S>;
+    type Signature = MssHypertreeSignature<S>;
+    type Error = MssError;
+
+    ///
+    /// Generate Public Key
+    ///
+    /// Root of the top tree.
+    ///
+    fn generate_public_key(&self) -> Self::PublicKey {
+        let layers = self.layers.borrow();
+        layers[layers.len() - 1].key.generate_public_key()
+    }
+
+    ///
+    /// Sign
+    ///
+    /// Moving to the next bottom tree generates it at once, along with the
+    /// trees above it that were exhausted as well. The new trees only replace
+    /// the current ones once all of them are generated and signed.
+    ///
+    fn sign(&self, message: &[Trit]) -> Result<Self::Signature, Self::Error> {
+        let mut layers = self.layers.borrow_mut();
+        if layers[0].key.sigs_remaining() == 0 {
+            self.next_trees(&mut layers)?;
+        }
+
+        let mut state = layers[0].key.sign(message)?.state;
+        for layer in layers.iter() {
+            state.extend_from_slice(&layer.root_signature);
+        }
+        Ok(MssHypertreeSignature {
+            state,
+            _sponge: PhantomData,
+        })
+    }
+}
+
+impl<S> MssHypertreeSignature<S> {
+    ///
+    /// MSS signatures, from the bottom layer up
+    ///
+    /// Each one is delimited by its depth field.
+    ///
+    pub fn layers(&self) -> Result<Vec<&[Trit]>, MssError> {
+        split(&self.state)
+    }
+}
+
+///
+/// Depth field of a MSS signature
+///
+fn signature_depth(signature: &[Trit]) -> i64 {
+    long_value(&signature[..4])
+}
+
+///
+/// Split a hypertree signature into its MSS signatures
+///
+/// Every layer uses trees of the same depth.
+///
+fn split(bytes: &[Trit]) -> Result<Vec<&[Trit]>, MssError> {
+    let mut parts: Vec<&[Trit]> = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        if rest.len() < MAM_MSS_SIGNATURE_HEADER_SIZE || parts.len() == MAM_MSS_MAX_LAYERS {
+            return Err(MssError::InvalidSignatureSize(bytes.len()));
+        }
+        let depth = signature_depth(rest);
+        if depth < 0 || depth > MAM_MSS_MAX_DEPTH as i64 {
+            return Err(MssError::InvalidDepth(depth));
+        }
+        if parts
+            .first()
+            .is_some_and(|first| signature_depth(first) != depth)
+        {
+            return Err(MssError::InvalidDepth(depth));
+        }
+        let size =
+            MAM_MSS_SIGNATURE_HEADER_SIZE + MAM_WOTS_SIGNATURE_SIZE + HASH_LEN * depth as usize;
+        if rest.len() < size {
+            return Err(MssError::InvalidSignatureSize(bytes.len()));
+        }
+        parts.push(&rest[..size]);
+        rest = &rest[size..];
+    }
+    if parts.is_empty() {
+        return Err(MssError::InvalidSignatureSize(0));
+    }
+    Ok(parts)
+}
+
+impl<S> Signature for MssHypertreeSignature<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    /// PublicKey Type
+    type PublicKey = MssPublicKey<S>;
+    type Error = MssError;
+    const MAX_SIZE: usize = MAM_MSS_MAX_HYPERTREE_SIGNATURE_SIZE;
+
+    ///
+    /// Recover Public Key
+    ///
+    /// Every recovered root is hashed into the message of the signature
+    /// above it. The layer count is not checked, see `verify_hypertree`.
+    ///
+    fn recover_public_key(&self, message: &[Trit]) -> Result<Self::PublicKey, Self::Error> {
+        let parts = split(&self.state)?;
+        let mut public_key =
+            MssSignature::<S>::from_bytes(parts[0])?.recover_public_key(message)?;
+        for (layer, part) in parts.iter().enumerate().skip(1) {
+            let root = root_message::<S>(layer - 1, &public_key.state)?;
+            public_key = MssSignature::<S>::from_bytes(part)?.recover_public_key(&root)?;
+        }
+        Ok(public_key)
+    }
+    ///
+    /// To Bytes
+    ///
+    fn to_bytes(&self) -> &[Trit] {
+        &self.state
+    }
+    ///
+    /// From Bytes
+    ///
+    fn from_bytes(bytes: &[Trit]) -> Result<Self, Self::Error> {
+        split(bytes)?;
+        check_trits(bytes).map_err(MssError::InvalidTrits)?;
+
+        Ok(MssHypertreeSignature {
+            state: bytes.to_vec(),
+            _sponge: PhantomData,
+        })
+    }
+}
+
+impl<S> MssPublicKey<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    ///
+    /// Verify a hypertree signature
+    ///
+    /// `self` is the root of the top tree. The signature must hold exactly
+    /// `layers` MSS signatures, a suffix of a valid signature would otherwise
+    /// verify the root of a lower tree. Their depth must match `self` when it
+    /// is known.
+    ///
+    pub fn verify_hypertree(
+        &self,
+        message: &[Trit],
+        signature: &MssHypertreeSignature<S>,
+        layers: usize,
+    ) -> Result<(), MssError> {
+        let parts = signature.layers()?;
+        if parts.len() != layers {
+            return Err(MssError::InvalidLayers(parts.len()));
+        }
+        let depth = signature_depth(parts[0]);
+        if self.h != 0 && depth != self.h as i64 {
+            return Err(MssError::InvalidDepth(depth));
+        }
+
+        let pk = signature.recover_public_key(message)?;
+        if !ct_eq(&self.state, &pk.state) {
+            return Err(MssError::PublicKeyMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{spongos::MamSpongos, wots::WotsPrivateKeyGenerator};
+    use iota_conversion::Trinary;
+
+    type Tree = MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>>;
+
+    const SEED: &str =
+        "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
+
+    #[test]
+    fn sign_across_trees() {
+        let message = SEED.trits();
+        let mut altered = message.clone();
+        altered[0] = if altered[0] == 1 { 0 } else { 1 };
+        let generator = MssHypertreeGenerator::new(Tree::new(1, 1), 3).unwrap();
+        let private_key = generator.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let public_key = private_key.generate_public_key();
+
+        for remaining in (1..=8).rev() {
+            assert_eq!(private_key.sigs_remaining(), remaining);
+            let signature = private_key.sign(&message).unwrap();
+            assert_eq!(signature.layers().unwrap().len(), 3);
+            assert!(public_key.verify_hypertree(&message, &signature, 3).is_ok());
+            assert_eq!(
+                public_key.verify_hypertree(&altered, &signature, 3),
+                Err(MssError::PublicKeyMismatch)
+            );
+
+            let decoded = MssHypertreeSignature::from_bytes(signature.to_bytes()).unwrap();
+            assert!(public_key.verify_hypertree(&message, &decoded, 3).is_ok());
+        }
+        assert_eq!(private_key.sigs_remaining(), 0);
+        assert_eq!(
+            private_key.sign(&message).unwrap_err(),
+            MssError::KeyExhausted
+        );
+    }
+
+    #[test]
+    fn reject_invalid_hypertrees() {
+        assert_eq!(
+            MssHypertreeGenerator::new(Tree::new(1, 1), 0).unwrap_err(),
+            MssError::InvalidLayers(0)
+        );
+        assert_eq!(
+            MssHypertreeGenerator::new(Tree::new(1, 1), MAM_MSS_MAX_LAYERS + 1).unwrap_err(),
+            MssError::InvalidLayers(MAM_MSS_MAX_LAYERS + 1)
+        );
+        assert!(MssHypertreeGenerator::new(Tree::new(20, 1), 3).is_ok());
+        assert_eq!(
+            MssHypertreeGenerator::new(Tree::new(20, 1), 4).unwrap_err(),
+            MssError::InvalidLayers(4)
+        );
+        assert_eq!(
+            MssHypertreeGenerator::new(Tree::new(0, 1), 2).unwrap_err(),
+            MssError::InvalidDepth(0)
+        );
+
+        let private_key = MssHypertreeGenerator::new(Tree::new(1, 1), 2)
+            .unwrap()
+            .generate(&SEED.trits(), &[0; 18])
+            .unwrap();
+        let bytes = private_key.sign(&SEED.trits()).unwrap().to_bytes().to_vec();
+        assert_eq!(
+            MssHypertreeSignature::<MamSpongos>::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            MssError::InvalidSignatureSize(bytes.len() - 1)
+        );
+        assert_eq!(
+            MssHypertreeSignature::<MamSpongos>::from_bytes(&[]).unwrap_err(),
+            MssError::InvalidSignatureSize(0)
+        );
+    }
+
+    #[test]
+    fn reject_truncated_signatures() {
+        let message = SEED.trits();
+        let generator = MssHypertreeGenerator::new(Tree::new(1, 1), 3).unwrap();
+        let private_key = generator.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let public_key = private_key.generate_public_key();
+        let signature = private_key.sign(&message).unwrap();
+        let parts = signature.layers().unwrap();
+
+        // the bottom root, as signed by the middle tree
+        let bottom = MssSignature::<MamSpongos>::from_bytes(parts[0])
+            .unwrap()
+            .recover_public_key(&message)
+            .unwrap();
+        let forged_message = root_message::<MamSpongos>(0, &bottom.state).unwrap();
+        let suffix = MssHypertreeSignature::from_bytes(&parts[1..].concat()).unwrap();
+        assert_eq!(
+            public_key.verify_hypertree(&forged_message, &suffix, 3),
+            Err(MssError::InvalidLayers(2))
+        );
+        assert_eq!(
+            public_key.verify_hypertree(&forged_message, &suffix, 2),
+            Err(MssError::PublicKeyMismatch)
+        );
+
+        let deeper = MssHypertreeGenerator::new(Tree::new(2, 1), 2)
+            .unwrap()
+            .generate(&SEED.trits(), &[0; 18])
+            .unwrap()
+            .sign(&message)
+            .unwrap();
+        let mixed = [parts[0], deeper.layers().unwrap()[1]].concat();
+        assert_eq!(
+            MssHypertreeSignature::<MamSpongos>::from_bytes(&mixed).unwrap_err(),
+            MssError::InvalidDepth(2)
+        );
+    }
+}
@@ -3,12 +3,16 @@
 //!
 //! Based on FMTSEQ => https://github.com/exaexa/codecrypt/blob/master/src/fmtseq.h
 //!
-use crate::definitions::{
-    ss::{PrivateKey, PrivateKeyGenerator, PublicKey},
-    Sponge,
+use crate::{
+    constants::trits_with_length,
+    definitions::{
+        ss::{PrivateKey, PrivateKeyGenerator, PublicKey},
+        Sponge, Trit,
+    },
+    mss::traversal::leaf_public_key,
 };
-use iota_conversion::{Trinary, Trit};
-use std::marker::PhantomData;
+use alloc::{string::String, vec, vec::Vec};
+use core::marker::PhantomData;
 
 ///
 /// HASH LENGTH
@@ -25,10 +29,8 @@ pub struct InternalPrivateKey<S, G> {
     pub seed: Vec<Trit>,
     /// Nonce
     pub nonce: Vec<Trit>,
-    /// Level count
-    pub level: usize,
-    /// SubTree Height
-    pub height: usize,
+    /// SubTree Heights, lowest level first
+    pub heights: Vec<usize>,
     /// Sigs used
     pub sigs_used: usize,
     /// Exists Subtrees
@@ -47,7 +49,7 @@ pub struct InternalPrivateKey<S, G> {
 ///
 /// Tree Stack Item
 ///
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct TreeStackItem {
     /// Level
     pub level: usize,
@@ -57,24 +59,14 @@ pub struct TreeStackItem {
     pub item: Vec<Trit>,
 }
 
-impl Default for TreeStackItem {
-    fn default() -> Self {
-        TreeStackItem {
-            level: 0,
-            pos: 0,
-            item: Vec::new(),
-        }
-    }
-}
-
 impl TreeStackItem {
     ///
     /// Create new TreeStackItem
     ///
     pub fn new(level: usize, pos: usize, item: &[Trit]) -> Self {
         TreeStackItem {
-            level: level,
-            pos: pos,
+            level,
+            pos,
             item: item.to_vec(),
         }
     }
@@ -90,12 +82,12 @@ where
     ///
     /// Initiate Mss PrivateKey
     ///
-    pub fn new(seed: &[Trit], nonce: &[Trit], height: usize, level: usize) -> Self {
+    pub fn new(seed: &[Trit], nonce: &[Trit], heights: &[usize]) -> Self {
+        let level = heights.len();
         InternalPrivateKey {
             seed: seed.to_vec(),
             nonce: nonce.to_vec(),
-            level: level,
-            height: height,
+            heights: heights.to_vec(),
             sigs_used: 0,
             exist: vec![Vec::new(); level],
             desired: vec![Vec::new(); level - 1],
@@ -106,34 +98,67 @@ where
         }
     }
 
+    ///
+    /// Tree depth
+    ///
+    pub fn depth(&self) -> usize {
+        self.heights.iter().sum()
+    }
+
+    ///
+    /// Tree level of the leaves of the subtrees at `level`
+    ///
+    fn offset(&self, level: usize) -> usize {
+        self.heights[..level].iter().sum()
+    }
+
+    ///
+    /// Subtree level holding the nodes at `tree_level`, and their height above its leaves
+    ///
+    fn locate(&self, tree_level: usize) -> Option<(usize, usize)> {
+        let mut offset = 0;
+        for (level, height) in self.heights.iter().enumerate() {
+            if tree_level < offset + height {
+                return Some((level, tree_level - offset));
+            }
+            offset += height;
+        }
+        None
+    }
+
+    ///
+    /// Size of a subtree at `level`, without its root
+    ///
+    fn subtree_size(&self, level: usize) -> usize {
+        ((1 << (self.heights[level] + 1)) - 2) * HASH_LEN
+    }
+
     ///
     /// Alloc exist tree
     ///
     pub(crate) fn alloc_exist(&mut self) {
-        let ts = (1 << (self.height + 1)) - 2;
-        for it in 0..self.level {
-            self.exist[it] = vec![0i8; ts * HASH_LEN];
+        for it in 0..self.exist.len() {
+            self.exist[it] = vec![0i8; self.subtree_size(it)];
         }
     }
     ///
     /// Alloc Desired
     ///
     pub(crate) fn alloc_desired(&mut self) {
-        for it in 0..self.level - 1 {
-            let ts = (1 << (self.height + 1)) - 2;
-            self.desired[it] = vec![0i8; ts * HASH_LEN];
+        for it in 0..self.desired.len() {
+            self.desired[it] = vec![0i8; self.subtree_size(it)];
         }
     }
     ///
     /// Store Exits
     ///
     pub(crate) fn store_exist(&mut self, item: &TreeStackItem) {
-        let level = item.level / self.height;
-        if level >= self.level {
+        let (level, local) = match self.locate(item.level) {
+            Some(found) => found,
             // top node
-            return;
-        }
-        let sublevel = self.height - (item.level % self.height);
+            None => return,
+        };
+        let sublevel = self.heights[level] - local;
         let sublev_width = 1 << sublevel;
         if item.pos >= sublev_width {
             // too far right
@@ -147,11 +172,12 @@ where
     /// Store Desired
     ///
     pub(crate) fn store_desired(&mut self, item: &TreeStackItem, did: usize) {
-        if (item.level / self.height) != did {
+        let local = match self.locate(item.level) {
+            Some((level, local)) if level == did => local,
             // too below or above
-            return;
-        }
-        let depth = self.height - (item.level % self.height);
+            _ => return,
+        };
+        let depth = self.heights[did] - local;
         if item.pos >= (1 << depth) {
             return;
         }
@@ -163,14 +189,16 @@ where
     /// Check Private Key
     ///
     pub(crate) fn check_privkey(&self) -> bool {
-        let ts: usize = (1 << (self.height + 1)) - 2;
+        if self.sigs_used >= 1 << self.depth() {
+            return false;
+        }
         // exist tree count is always L
-        if self.exist.len() != self.level {
+        if self.exist.len() != self.heights.len() {
             return false;
         }
 
         for it in 0..self.exist.len() {
-            if self.exist[it].len() != ts * HASH_LEN {
+            if self.exist[it].len() != self.subtree_size(it) {
                 return false;
             }
         }
@@ -183,17 +211,17 @@ where
         }
 
         for it in 0..self.desired.len() {
-            if self.desired[it].len() != ts * HASH_LEN {
+            if self.desired[it].len() != self.subtree_size(it) {
                 return false;
             }
         }
 
-        return true;
+        true
     }
 
     pub(crate) fn update_private_key(&mut self) {
         for it in 0..self.desired.len() {
-            let d_h = (it + 1) * self.height;
+            let d_h = self.offset(it + 1);
             let d_leaves = 1 << d_h;
             if self.desired_progress[it] >= d_leaves {
                 continue; //already done
@@ -202,14 +230,9 @@ where
             let d_startpos = (1 + (self.sigs_used >> d_h)) << d_h;
             let leaf_id = d_startpos + self.desired_progress[it];
 
-            let trits = (leaf_id as i64).trits_with_length(6);
-            let w_kg = G::default();
-            let wots_priv_key = w_kg
-                .generate(&self.seed, &[&self.nonce[..], &trits[..]].concat())
-                .unwrap();
-            let pk = wots_priv_key.generate_public_key();
+            let pk = leaf_public_key::<S, G>(&self.seed, &self.nonce, leaf_id).unwrap();
 
-            let item = TreeStackItem::new(0, self.desired_progress[it], pk.to_bytes());
+            let item = TreeStackItem::new(0, self.desired_progress[it], &pk);
             self.desired_stack[it].push(item.clone());
 
             self.store_desired(&item, it);
@@ -245,17 +268,16 @@ where
 
         let next_sigs_used = self.sigs_used + 1;
         let subtree_changes = self.sigs_used ^ next_sigs_used;
-        let one_subtree_mask = (1 << self.height) - 1;
 
         // go from the topmost subtree.
-        for it in 0..self.level {
-            let idx = self.level - it - 1;
+        for idx in (0..self.heights.len()).rev() {
             // ignore unused top levels
-            if idx > self.desired.len() {
+            if idx >= self.desired.len() {
                 continue;
             }
+            let d_h = self.offset(idx + 1);
             // if nothing changed, do nothing
-            if ((subtree_changes >> (self.height * (1 + idx))) & one_subtree_mask) == 0 {
+            if (subtree_changes >> d_h) == 0 {
                 continue;
             }
 
@@ -266,9 +288,8 @@ where
 
             // if there aren't more desired subtrees on this level,
             // strip it off.
-            let next_subtree_start =
-                (1 + (next_sigs_used >> ((1 + idx) * self.height))) << ((1 + idx) * self.height);
-            if next_subtree_start >= (1 << (self.height * self.level)) {
+            let next_subtree_start = (1 + (next_sigs_used >> d_h)) << d_h;
+            if next_subtree_start >= (1 << self.depth()) {
                 self.desired.resize_with(idx, Default::default);
                 self.desired_stack.resize_with(idx, Default::default);
                 self.desired_progress.resize_with(idx, Default::default);
@@ -281,13 +302,13 @@ where
     /// Retrieve the Authentication Path
     ///
     pub fn apath(&self) -> Vec<Trit> {
-        let t_height = self.height * self.level;
+        let t_height = self.depth();
         let mut pos = self.sigs_used;
         let mut p = vec![0i8; t_height * HASH_LEN];
 
         for it in 0..t_height {
-            let exid = it / self.height;
-            let exlev = self.height - (it % self.height);
+            let (exid, local) = self.locate(it).unwrap();
+            let exlev = self.heights[exid] - local;
             // flip the last bit of pos so it gets the neighbor
             let expos = (pos ^ 1) % (1 << exlev);
             let ep = expos + (1 << exlev) - 2;
@@ -304,9 +325,9 @@ where
     ///
     pub fn skn(&self) -> [i8; 18] {
         let mut encoded_skn = [0i8; 18];
-        let t_height = self.height * self.level;
-        let t_depth = (t_height as i64).trits_with_length(4);
-        let t_skn = (self.sigs_used as i64).trits_with_length(14);
+        let t_height = self.depth();
+        let t_depth = trits_with_length(t_height as i64, 4);
+        let t_skn = trits_with_length(self.sigs_used as i64, 14);
 
         encoded_skn[..4].copy_from_slice(&t_depth[..]);
         encoded_skn[4..].copy_from_slice(&t_skn[..]);
@@ -317,6 +338,6 @@ where
     /// Sigs Remaning
     ///
     pub fn sigs_remaining(&self) -> usize {
-        (1 << (self.height * self.level)) - self.sigs_used
+        (1 << self.depth()) - self.sigs_used
     }
 }
@@ -3,32 +3,150 @@
 //!
 //! Based on FMTSEQ => https://github.com/exaexa/codecrypt/blob/master/src/fmtseq.h
 //!
+mod cache;
+mod hypertree;
 mod internal;
+mod traversal;
+
+pub use cache::CachedMssVerifier;
+pub use hypertree::{
+    MssHypertreeGenerator, MssHypertreePrivateKey, MssHypertreeSignature,
+    MAM_MSS_MAX_HYPERTREE_SIGNATURE_SIZE, MAM_MSS_MAX_LAYERS,
+};
+pub use traversal::MssTraversal;
 
 use crate::{
+    constants::long_value as trist_to_value,
     definitions::{
+        check_trits, ct_eq,
         ss::{PrivateKey, PrivateKeyGenerator, PublicKey, Signature},
-        Sponge,
+        Sponge, Trit,
     },
-    mss::internal::{InternalPrivateKey, TreeStackItem},
-    wots::WotsSignature,
+    mss::{
+        cache::NodeCache,
+        internal::{InternalPrivateKey, TreeStackItem},
+        traversal::{leaf_nonce, leaf_public_key, subtree_root, BdsTraversal, Traversal},
+    },
+    wots::{WotsError, WotsSignature, MAM_WOTS_SIGNATURE_SIZE},
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
 };
-use iota_conversion::{long_value as trist_to_value, Trinary, Trit};
-use std::{cell::RefCell, marker::PhantomData};
+use core::{cell::RefCell, fmt, marker::PhantomData};
 
 ///
 /// HASH LENGTH
 ///
 const HASH_LEN: usize = 243;
 
+/// Size of a MSS public key
+pub const MAM_MSS_PUBLIC_KEY_SIZE: usize = HASH_LEN;
+/// Largest supported MSS depth
+///
+/// The 4-trit depth field holds up to 40, the 14-trit skn up to 2^21 leaves.
+pub const MAM_MSS_MAX_DEPTH: usize = 20;
+/// Size of the signature header, the 4-trit depth and the 14-trit leaf index (skn)
+pub const MAM_MSS_SIGNATURE_HEADER_SIZE: usize = 18;
+/// Largest size of a MSS signature
+pub const MAM_MSS_MAX_SIGNATURE_SIZE: usize =
+    MAM_MSS_SIGNATURE_HEADER_SIZE + MAM_WOTS_SIGNATURE_SIZE + HASH_LEN * MAM_MSS_MAX_DEPTH;
+
+///
+/// MSS Error
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MssError {
+    /// Public key is not `MAM_MSS_PUBLIC_KEY_SIZE` trits long
+    InvalidPublicKeySize(usize),
+    /// Signature size does not match its depth, or exceeds `MAM_MSS_MAX_SIGNATURE_SIZE`
+    InvalidSignatureSize(usize),
+    /// Depth is negative or above `MAM_MSS_MAX_DEPTH`, or a private key depth is 0
+    InvalidDepth(i64),
+    /// Subtree of height 0
+    EmptySubtree,
+    /// Hypertree layer count is 0, above `MAM_MSS_MAX_LAYERS`, or too many for its tree indexes
+    InvalidLayers(usize),
+    /// Leaf index (skn) field is out of the tree
+    InvalidSkn(i64),
+    /// Authentication path does not hold one hash per level of the tree
+    InvalidAuthPathSize {
+        /// Depth field of the signature
+        depth: usize,
+        /// Length of the authentication path in trits
+        len: usize,
+    },
+    /// Trits outside `-1..=1`
+    InvalidTrits(String),
+    /// Every leaf of the private key was used
+    KeyExhausted,
+    /// WOTS key generation failure
+    KeyGeneration(String),
+    /// WOTS failure
+    Wots(WotsError),
+    /// Recovered public key does not match
+    PublicKeyMismatch,
+    /// Sponge failure
+    Sponge(String),
+}
+
+impl fmt::Display for MssError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MssError::InvalidPublicKeySize(len) => write!(
+                f,
+                "MSS public key must be {} trits long, got {}",
+                MAM_MSS_PUBLIC_KEY_SIZE, len
+            ),
+            MssError::InvalidSignatureSize(len) => {
+                write!(f, "Invalid MSS signature size {}", len)
+            }
+            MssError::InvalidDepth(d) => write!(
+                f,
+                "MSS depth must be in 0..={}, got {}",
+                MAM_MSS_MAX_DEPTH, d
+            ),
+            MssError::EmptySubtree => write!(f, "MSS subtrees must be at least one level high"),
+            MssError::InvalidLayers(layers) => {
+                write!(f, "Invalid MSS hypertree layer count {}", layers)
+            }
+            MssError::InvalidSkn(skn) => write!(f, "MSS leaf index {} is out of the tree", skn),
+            MssError::InvalidAuthPathSize { depth, len } => write!(
+                f,
+                "MSS signature of depth {} must carry a {} trits authentication path, got {}",
+                depth,
+                HASH_LEN * depth,
+                len
+            ),
+            MssError::InvalidTrits(e) => write!(f, "{}", e),
+            MssError::KeyExhausted => write!(f, "MSS private key exhausted"),
+            MssError::KeyGeneration(e) => write!(f, "WOTS key generation error: {}", e),
+            MssError::Wots(e) => write!(f, "{}", e),
+            MssError::PublicKeyMismatch => write!(f, "MSS public key mismatch"),
+            MssError::Sponge(e) => write!(f, "Sponge error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MssError {}
+
+impl From<WotsError> for MssError {
+    fn from(e: WotsError) -> Self {
+        MssError::Wots(e)
+    }
+}
+
 /// MSS PrivateKey Generator
 ///
 #[derive(Debug)]
 pub struct MssPrivateKeyGenerator<S, G> {
-    /// Subtree Height
-    height: usize,
-    /// Tree Level
-    level: usize,
+    /// Subtree Heights, lowest level first
+    heights: Vec<usize>,
+    /// Traversal
+    traversal: MssTraversal,
     /// Sponge
     _sponge: PhantomData<S>,
     _generator: PhantomData<G>,
@@ -41,7 +159,7 @@ pub struct MssPrivateKeyGenerator<S, G> {
 #[derive(Clone)]
 pub struct MssPrivateKey<S, G> {
     /// Intenal Implementation
-    i_mt: RefCell<InternalPrivateKey<S, G>>,
+    i_mt: RefCell<Traversal<S, G>>,
     /// Public Key
     root: Vec<Trit>,
     /// Market Data
@@ -75,6 +193,34 @@ pub struct MssSignature<S> {
     _sponge: PhantomData<S>,
 }
 
+///
+/// Parsed MSS Signature
+///
+/// Fields of a signature, borrowed from its encoding.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedMssSignature<'a> {
+    /// Depth of the Merkle tree
+    pub depth: usize,
+    /// Index of the signing leaf (skn)
+    pub skn: usize,
+    /// WOTS signature of the message
+    pub wots_signature: &'a [Trit],
+    /// Authentication path, one sibling hash per level from the leaf up
+    pub auth_path: Vec<&'a [Trit]>,
+}
+
+impl<S, G> Clone for MssPrivateKeyGenerator<S, G> {
+    fn clone(&self) -> Self {
+        MssPrivateKeyGenerator {
+            heights: self.heights.clone(),
+            traversal: self.traversal,
+            _sponge: PhantomData,
+            _generator: PhantomData,
+        }
+    }
+}
+
 impl<S, G> PrivateKeyGenerator<S> for MssPrivateKeyGenerator<S, G>
 where
     S: Sponge<Error = String> + Default,
@@ -90,29 +236,36 @@ where
     /// Arguments:
     ///     `seed`: Secret Key
     ///     `nonce`: Nonce
-    ///     `subtree_height`: SubTree Height
-    ///     `level`: level count
     ///
     fn generate(&self, seed: &[Trit], nonce: &[Trit]) -> Result<Self::PrivateKey, String> {
+        check_heights(&self.heights).map_err(|e| e.to_string())?;
         let mut spongos = S::default();
-        let height = self.height * self.level;
-        let mut i_mt = InternalPrivateKey::new(&seed, &nonce, self.height, self.level);
+        let height = self.depth();
+        let mut i_mt = match self.traversal {
+            MssTraversal::Subtrees => {
+                let mut mt = InternalPrivateKey::new(seed, nonce, &self.heights);
+                mt.alloc_exist();
+                Traversal::Subtrees(mt)
+            }
+            MssTraversal::Bds { retain } if retain <= height => {
+                Traversal::Bds(BdsTraversal::new(seed, nonce, height, retain))
+            }
+            MssTraversal::Bds { retain } => {
+                return Err(format!(
+                    "BDS cannot retain {} levels of a tree of depth {}",
+                    retain, height
+                ))
+            }
+        };
 
         let mut stk: Vec<TreeStackItem> = Vec::with_capacity(height + 1);
         let sigs: usize = 1 << height;
 
-        i_mt.alloc_exist();
-        let wots_kgen = G::default();
-
         for it in 0..sigs {
-            let trits = (it as i64).trits_with_length(6);
-            let wots_priv_key = wots_kgen
-                .generate(&seed, &[&nonce[..], &trits[..]].concat())
-                .unwrap();
-            let pk = wots_priv_key.generate_public_key();
+            let pk = leaf_public_key::<S, G>(seed, nonce, it)?;
 
-            stk.push(TreeStackItem::new(0, it, pk.to_bytes()));
-            i_mt.store_exist(&stk[stk.len() - 1]);
+            stk.push(TreeStackItem::new(0, it, &pk));
+            i_mt.store(&stk[stk.len() - 1]);
 
             loop {
                 if stk.len() < 2 {
@@ -133,11 +286,13 @@ where
                     .unwrap();
 
                 stk.push(TreeStackItem::new(l, p, &hash));
-                i_mt.store_exist(&stk[stk.len() - 1]);
+                i_mt.store(&stk[stk.len() - 1]);
             }
         }
 
-        i_mt.alloc_desired();
+        if let Traversal::Subtrees(mt) = &mut i_mt {
+            mt.alloc_desired();
+        }
         Ok(MssPrivateKey::new(i_mt, stk[stk.len() - 1].item.clone()))
     }
 }
@@ -159,26 +314,156 @@ where
     ///     level Level of Merkle Tree
     ///
     pub fn new(height: usize, level: usize) -> Self {
+        Self::with_traversal(height, level, MssTraversal::default())
+    }
+
+    ///
+    /// Create MSS Private Key Generator from subtree heights
+    ///
+    /// The depth of this tree is the sum of `heights`, given from the lowest
+    /// level up. Subtrees may have different heights.
+    ///
+    pub fn with_heights(heights: &[usize]) -> Result<Self, MssError> {
+        check_heights(heights)?;
+        Ok(MssPrivateKeyGenerator {
+            heights: heights.to_vec(),
+            traversal: MssTraversal::default(),
+            _sponge: PhantomData,
+            _generator: PhantomData,
+        })
+    }
+
+    ///
+    /// Create MSS Private Key Generator with a traversal
+    ///
+    /// `MssTraversal::Bds` keeps memory logarithmic in the number of leaves,
+    /// for deep trees on constrained devices.
+    ///
+    pub fn with_traversal(height: usize, level: usize, traversal: MssTraversal) -> Self {
         MssPrivateKeyGenerator {
-            height,
-            level,
+            heights: vec![height; level],
+            traversal,
             _sponge: PhantomData,
             _generator: PhantomData,
         }
     }
+
+    ///
+    /// Traversal
+    ///
+    pub fn traversal(&self) -> MssTraversal {
+        self.traversal
+    }
+
+    ///
+    /// Subtree Heights, lowest level first
+    ///
+    pub fn heights(&self) -> &[usize] {
+        &self.heights
+    }
+
+    ///
+    /// Depth of the generated trees
+    ///
+    pub fn depth(&self) -> usize {
+        self.heights.iter().sum()
+    }
+
     ///
     /// Create MSS Private Key Generator from Depth
     ///
+    /// About `log2(depth)` high subtrees share exactly `depth` levels, the
+    /// lower ones being one level higher when it does not divide evenly.
+    ///
     /// Arguments
-    ///     depth Mss depth
+    ///     depth Mss depth, in `1..=MAM_MSS_MAX_DEPTH`
     ///
-    pub fn from_depth(depth: usize) -> Self {
-        let h = (depth as f32).log2().floor();
-        let level = (depth as f32 / h).round();
-        Self::new(h as usize, level as usize)
+    pub fn from_depth(depth: usize) -> Result<Self, MssError> {
+        if depth == 0 || depth > MAM_MSS_MAX_DEPTH {
+            return Err(MssError::InvalidDepth(depth as i64));
+        }
+        let h = ((usize::BITS - 1 - depth.leading_zeros()) as usize).max(1);
+        let level = (depth + h / 2) / h;
+        let heights: Vec<usize> = (0..level)
+            .map(|it| depth / level + usize::from(it < depth % level))
+            .collect();
+        Self::with_heights(&heights)
+    }
+
+    ///
+    /// Public Key only
+    ///
+    /// Same root as `generate`, without the signing state: leaves are streamed
+    /// through a treehash stack holding at most one node per level.
+    ///
+    pub fn public_key_only(
+        &self,
+        seed: &[Trit],
+        nonce: &[Trit],
+    ) -> Result<MssPublicKey<S>, String> {
+        check_heights(&self.heights).map_err(|e| e.to_string())?;
+        let depth = self.depth();
+        Ok(MssPublicKey {
+            state: subtree_root::<S, G>(seed, nonce, depth, 0)?,
+            h: depth,
+            _sponge: PhantomData,
+        })
+    }
+
+    ///
+    /// Public Key only, in parallel
+    ///
+    /// Same as `public_key_only`, every thread streams the leaves of its own
+    /// subtree, whose roots are then hashed up to the root.
+    ///
+    #[cfg(feature = "parallel")]
+    pub fn par_public_key_only(
+        &self,
+        seed: &[Trit],
+        nonce: &[Trit],
+    ) -> Result<MssPublicKey<S>, String> {
+        use rayon::prelude::*;
+
+        check_heights(&self.heights).map_err(|e| e.to_string())?;
+        let depth = self.depth();
+        let split = (rayon::current_num_threads()
+            .next_power_of_two()
+            .trailing_zeros() as usize)
+            .min(depth);
+        let mut nodes = (0..1_usize << split)
+            .into_par_iter()
+            .map(|pos| subtree_root::<S, G>(seed, nonce, depth - split, pos))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let mut spongos = S::default();
+        while nodes.len() > 1 {
+            nodes = nodes
+                .chunks(2)
+                .map(|pair| spongos.hash(&pair.concat(), HASH_LEN))
+                .collect::<Result<_, String>>()?;
+        }
+        Ok(MssPublicKey {
+            state: nodes.remove(0),
+            h: depth,
+            _sponge: PhantomData,
+        })
     }
 }
 
+///
+/// Check the subtree heights of a generator
+///
+fn check_heights(heights: &[usize]) -> Result<(), MssError> {
+    if heights.contains(&0) {
+        return Err(MssError::EmptySubtree);
+    }
+    let depth: usize = heights.iter().sum();
+    if depth == 0 || depth > MAM_MSS_MAX_DEPTH {
+        return Err(MssError::InvalidDepth(depth as i64));
+    }
+    Ok(())
+}
+
 impl<S, G> PrivateKey for MssPrivateKey<S, G>
 where
     S: Sponge<Error = String> + Default,
@@ -186,42 +471,46 @@ where
     <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey + Clone,
     <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
     <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Signature: Signature,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::Error: Into<MssError>,
 {
     type PublicKey = MssPublicKey<S>;
     type Signature = MssSignature<S>;
+    type Error = MssError;
     ///
     /// Generate Public Key
     ///
     fn generate_public_key(&self) -> Self::PublicKey {
         MssPublicKey {
             state: self.root.to_vec(),
-            h: self.i_mt.borrow().height * self.i_mt.borrow().level,
+            h: self.i_mt.borrow().depth(),
             _sponge: PhantomData,
         }
     }
 
-    fn sign(&self, message: &[i8]) -> Result<Self::Signature, String> {
+    fn sign(&self, message: &[Trit]) -> Result<Self::Signature, Self::Error> {
         let mut i_mt = self.i_mt.borrow_mut();
-        let t_height = i_mt.height * i_mt.level;
-        let mut signature_state = vec![0_i8; 18 + 13122 + HASH_LEN * t_height];
-
         if !i_mt.check_privkey() {
-            return Err("Secret Key error".to_owned());
+            return Err(MssError::KeyExhausted);
         }
 
         let wots_kgen = G::default();
-        signature_state[0..18].copy_from_slice(&i_mt.skn());
-
-        let trits = (i_mt.sigs_used as i64).trits_with_length(6);
         let wots_priv_key = wots_kgen
-            .generate(&i_mt.seed, &[&i_mt.nonce[..], &trits[..]].concat())
-            .unwrap();
-        let signature = wots_priv_key.sign(message).unwrap();
+            .generate(i_mt.seed(), &leaf_nonce(i_mt.nonce(), i_mt.sigs_used()))
+            .map_err(MssError::KeyGeneration)?;
+        let signature = wots_priv_key.sign(message).map_err(Into::into)?;
+        // the header has no field for WOTS parameters, only MAM2 signatures fit
+        if signature.to_bytes().len() != MAM_WOTS_SIGNATURE_SIZE {
+            return Err(WotsError::InvalidSignatureSize(signature.to_bytes().len()).into());
+        }
 
-        signature_state[18..(18 + 13122)].copy_from_slice(&signature.to_bytes());
-        signature_state[(18 + 13122)..].copy_from_slice(&i_mt.apath()[..]);
+        let mut signature_state = Vec::with_capacity(
+            MAM_MSS_SIGNATURE_HEADER_SIZE + MAM_WOTS_SIGNATURE_SIZE + HASH_LEN * i_mt.depth(),
+        );
+        signature_state.extend(i_mt.skn());
+        signature_state.extend_from_slice(signature.to_bytes());
+        signature_state.extend(i_mt.apath());
 
-        i_mt.update_private_key();
+        i_mt.update_private_key().map_err(MssError::KeyGeneration)?;
 
         Ok(MssSignature {
             state: signature_state,
@@ -235,31 +524,95 @@ where
     S: Sponge<Error = String> + Default,
 {
     type Signature = MssSignature<S>;
+    type Error = MssError;
+    const SIZE: usize = MAM_MSS_PUBLIC_KEY_SIZE;
+
     ///
     /// Verify
     ///
-    fn verify(&self, message: &[i8], signature: &Self::Signature) -> bool {
-        let pk = signature.recover_public_key(message);
-        self.state
-            .iter()
-            .zip(pk.to_bytes().iter())
-            .all(|(st, pk)| st == pk)
+    fn verify(&self, message: &[Trit], signature: &Self::Signature) -> Result<(), Self::Error> {
+        let pk = signature.recover_public_key(message)?;
+        if !ct_eq(&self.state, &pk.state) {
+            return Err(MssError::PublicKeyMismatch);
+        }
+        Ok(())
     }
     ///
     /// To Bytes
     ///
-    fn to_bytes(&self) -> &[i8] {
+    fn to_bytes(&self) -> &[Trit] {
         &self.state
     }
     ///
-    /// To Bytes
+    /// From Bytes
     ///
-    fn form_bytes(bytes: &[i8]) -> Self {
-        MssPublicKey {
+    /// The depth is not part of the public key, it is only known once a
+    /// signature was recovered.
+    ///
+    fn from_bytes(bytes: &[Trit]) -> Result<Self, Self::Error> {
+        if bytes.len() != MAM_MSS_PUBLIC_KEY_SIZE {
+            return Err(MssError::InvalidPublicKeySize(bytes.len()));
+        }
+        check_trits(bytes).map_err(MssError::InvalidTrits)?;
+
+        Ok(MssPublicKey {
             state: bytes.to_vec(),
             h: 0,
             _sponge: PhantomData,
-        }
+        })
+    }
+}
+
+impl<S> MssPublicKey<S> {
+    ///
+    /// Depth of the Merkle tree behind this public key
+    ///
+    pub fn depth(&self) -> usize {
+        self.h
+    }
+}
+
+impl<S> MssPublicKey<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    ///
+    /// Verify Batch
+    ///
+    /// Verify many `(message, signature)` pairs, one result per pair. The
+    /// spongos is shared and the authentication path nodes verified for a leaf
+    /// are reused by the following ones, so pairs should come in leaf order.
+    ///
+    pub fn verify_batch(&self, items: &[(&[Trit], &MssSignature<S>)]) -> Vec<Result<(), MssError>> {
+        let mut spongos = S::default();
+        let mut cache = NodeCache::default();
+        items
+            .iter()
+            .map(|(message, signature)| cache.verify(&mut spongos, &self.state, message, signature))
+            .collect()
+    }
+
+    ///
+    /// Verify Batch in parallel
+    ///
+    /// Same as `verify_batch`, every thread verifies a contiguous run of the
+    /// pairs with its own spongos and cache.
+    ///
+    #[cfg(feature = "parallel")]
+    pub fn par_verify_batch(
+        &self,
+        items: &[(&[Trit], &MssSignature<S>)],
+    ) -> Vec<Result<(), MssError>>
+    where
+        S: Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        let run = (items.len() / rayon::current_num_threads()).max(1);
+        items
+            .par_chunks(run)
+            .flat_map_iter(|chunk| self.verify_batch(chunk))
+            .collect()
     }
 }
 
@@ -276,67 +629,105 @@ where
     }
 }
 
+impl<S> MssSignature<S> {
+    ///
+    /// Parse
+    ///
+    /// Decode the 4-trit depth, the 14-trit leaf index, the WOTS signature and
+    /// the authentication path, checking each field against the others.
+    ///
+    pub fn parse(&self) -> Result<ParsedMssSignature<'_>, MssError> {
+        let min_size = MAM_MSS_SIGNATURE_HEADER_SIZE + MAM_WOTS_SIGNATURE_SIZE;
+        if self.state.len() < min_size {
+            return Err(MssError::InvalidSignatureSize(self.state.len()));
+        }
+
+        let depth = trist_to_value(&self.state[..4]);
+        if depth < 0 || depth > MAM_MSS_MAX_DEPTH as i64 {
+            return Err(MssError::InvalidDepth(depth));
+        }
+        let skn = trist_to_value(&self.state[4..MAM_MSS_SIGNATURE_HEADER_SIZE]);
+        if skn < 0 || skn >= (1 << depth) {
+            return Err(MssError::InvalidSkn(skn));
+        }
+
+        let depth = depth as usize;
+        let auth_path = &self.state[min_size..];
+        if auth_path.len() != HASH_LEN * depth {
+            return Err(MssError::InvalidAuthPathSize {
+                depth,
+                len: auth_path.len(),
+            });
+        }
+
+        Ok(ParsedMssSignature {
+            depth,
+            skn: skn as usize,
+            wots_signature: &self.state[MAM_MSS_SIGNATURE_HEADER_SIZE..min_size],
+            auth_path: auth_path.chunks(HASH_LEN).collect(),
+        })
+    }
+}
+
 impl<S> Signature for MssSignature<S>
 where
     S: Sponge<Error = String> + Default,
 {
     /// PublicKey Type
     type PublicKey = MssPublicKey<S>;
+    type Error = MssError;
+    const MAX_SIZE: usize = MAM_MSS_MAX_SIGNATURE_SIZE;
 
     ///
     /// Recover Public Key
     ///
-    fn recover_public_key(&self, message: &[i8]) -> Self::PublicKey {
-        if self.state.len() < 18 + 13122 {
-            return Self::PublicKey::default();
-        }
-        let d = trist_to_value(&self.state[..4].to_vec());
-        let mut skn = trist_to_value(&self.state[4..18].to_vec());
-
-        if (d < 0)
-            || (skn < 0)
-            || (skn >= (1 << d))
-            || (self.state.len() != (18 + 13122 + 243 * d) as usize)
-        {
-            return Self::PublicKey::default();
-        }
+    fn recover_public_key(&self, message: &[Trit]) -> Result<Self::PublicKey, Self::Error> {
+        let parsed = self.parse()?;
+        let mut skn = parsed.skn;
 
-        let wots: WotsSignature<S> = WotsSignature::form_bytes(&self.state[18..(18 + 13122)]);
-        let mut t = wots.recover_public_key(message).to_bytes().to_vec();
-        let mut p = self.state[(18 + 13122)..].to_vec();
+        let wots: WotsSignature<S> = WotsSignature::from_bytes(parsed.wots_signature)?;
+        let mut t = wots.recover_public_key(message)?.to_bytes().to_vec();
         let mut spongos = S::default();
 
-        for _ in 0..d {
+        for node in parsed.auth_path.iter() {
             if skn % 2 == 0 {
-                t = [&t, &p[..HASH_LEN]].concat();
+                t = [&t, *node].concat();
             } else {
-                t = [&p[..HASH_LEN], &t].concat();
+                t = [*node, &t].concat();
             }
-            t = spongos.hash(&t, HASH_LEN).unwrap();
-            p = p[HASH_LEN..].to_vec();
-            skn = ((skn / 2) as f32).floor() as i64;
+            t = spongos.hash(&t, HASH_LEN).map_err(MssError::Sponge)?;
+            skn /= 2;
         }
 
-        return MssPublicKey {
+        Ok(MssPublicKey {
             state: t,
-            h: d as usize,
+            h: parsed.depth,
             _sponge: PhantomData,
-        };
+        })
     }
     ///
     /// To Bytes
     ///
-    fn to_bytes(&self) -> &[i8] {
+    fn to_bytes(&self) -> &[Trit] {
         &self.state
     }
     ///
-    /// To Bytes
+    /// From Bytes
     ///
-    fn form_bytes(bytes: &[i8]) -> Self {
-        MssSignature {
+    fn from_bytes(bytes: &[Trit]) -> Result<Self, Self::Error> {
+        let min_size = MAM_MSS_SIGNATURE_HEADER_SIZE + MAM_WOTS_SIGNATURE_SIZE;
+        if bytes.len() < min_size
+            || bytes.len() > MAM_MSS_MAX_SIGNATURE_SIZE
+            || !(bytes.len() - min_size).is_multiple_of(HASH_LEN)
+        {
+            return Err(MssError::InvalidSignatureSize(bytes.len()));
+        }
+        check_trits(bytes).map_err(MssError::InvalidTrits)?;
+
+        Ok(MssSignature {
             state: bytes.to_vec(),
             _sponge: PhantomData,
-        }
+        })
     }
 }
 
@@ -350,21 +741,29 @@ where
     ///
     /// Initiate Mss PrivateKey
     ///
-    pub fn new(mt: InternalPrivateKey<S, G>, root: Vec<Trit>) -> Self {
+    pub(crate) fn new(mt: Traversal<S, G>, root: Vec<Trit>) -> Self {
         MssPrivateKey {
             i_mt: RefCell::new(mt),
-            root: root,
+            root,
             _sponge: PhantomData,
             _gen: PhantomData,
         }
     }
+
+    ///
+    /// Number of signatures left
+    ///
+    pub fn sigs_remaining(&self) -> usize {
+        self.i_mt.borrow().sigs_remaining()
+    }
 }
 
 #[cfg(test)]
 mod should {
     use super::*;
-    use crate::{spongos::MamSpongos, wots::WotsPrivateKeyGenerator};
+    use crate::{constants::trits_with_length, spongos::MamSpongos, wots::WotsPrivateKeyGenerator};
     use iota_conversion::Trinary;
+    use proptest::prelude::*;
 
     const SEED: &str =
         "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
@@ -392,7 +791,101 @@ mod should {
         let private_key = mss_kg.generate(&seed_trits, &nonce).unwrap();
         let signature = private_key.sign(&message).unwrap();
 
-        assert_eq!(signature.to_bytes().len(), 18 + 13122 + 243 * 4);
+        assert_eq!(
+            signature.to_bytes().len(),
+            MAM_MSS_SIGNATURE_HEADER_SIZE + MAM_WOTS_SIGNATURE_SIZE + 243 * 4
+        );
+    }
+
+    #[test]
+    fn map_depth_exactly() {
+        type Generator = MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>>;
+        for depth in 1..=MAM_MSS_MAX_DEPTH {
+            let heights = Generator::from_depth(depth).unwrap().heights().to_vec();
+            assert_eq!(heights.iter().sum::<usize>(), depth);
+            assert!(heights.windows(2).all(|w| w[0] == w[1] || w[0] == w[1] + 1));
+        }
+        assert_eq!(Generator::from_depth(1).unwrap().heights(), [1]);
+        assert_eq!(Generator::from_depth(5).unwrap().heights(), [2, 2, 1]);
+        assert_eq!(Generator::from_depth(20).unwrap().heights(), [4; 5]);
+
+        assert_eq!(
+            Generator::from_depth(0).unwrap_err(),
+            MssError::InvalidDepth(0)
+        );
+        assert_eq!(
+            Generator::from_depth(MAM_MSS_MAX_DEPTH + 1).unwrap_err(),
+            MssError::InvalidDepth(MAM_MSS_MAX_DEPTH as i64 + 1)
+        );
+        assert_eq!(
+            Generator::with_heights(&[2, 0]).unwrap_err(),
+            MssError::EmptySubtree
+        );
+        assert_eq!(
+            Generator::with_heights(&[]).unwrap_err(),
+            MssError::InvalidDepth(0)
+        );
+        assert!(Generator::new(0, 3)
+            .generate(&SEED.trits(), &[0; 18])
+            .is_err());
+    }
+
+    #[test]
+    fn sign_with_uneven_subtrees() {
+        let message = SEED.trits();
+        let nonce = [0; 18];
+        let generate = |generator: MssPrivateKeyGenerator<
+            MamSpongos,
+            WotsPrivateKeyGenerator<MamSpongos>,
+        >| generator.generate(&SEED.trits(), &nonce).unwrap();
+        let keys = [
+            generate(MssPrivateKeyGenerator::new(3, 1)),
+            generate(MssPrivateKeyGenerator::with_heights(&[1, 2]).unwrap()),
+            generate(MssPrivateKeyGenerator::with_heights(&[2, 1]).unwrap()),
+        ];
+        let public_key = keys[0].generate_public_key();
+
+        for _ in 0..1 << 3 {
+            let signature = keys[0].sign(&message).unwrap();
+            assert!(public_key.verify(&message, &signature).is_ok());
+            for key in keys[1..].iter() {
+                assert_eq!(key.sign(&message).unwrap().to_bytes(), signature.to_bytes());
+            }
+        }
+        for key in keys.iter() {
+            assert_eq!(key.sign(&message).unwrap_err(), MssError::KeyExhausted);
+        }
+    }
+
+    #[test]
+    fn derive_public_key_only() {
+        let nonce = [0; 18];
+        for generator in [
+            MssPrivateKeyGenerator::<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>>::new(1, 3),
+            MssPrivateKeyGenerator::with_heights(&[2, 1]).unwrap(),
+        ]
+        .iter()
+        {
+            let public_key = generator.public_key_only(&SEED.trits(), &nonce).unwrap();
+            let expected = generator
+                .generate(&SEED.trits(), &nonce)
+                .unwrap()
+                .generate_public_key();
+            assert_eq!(public_key.state, expected.state);
+            assert_eq!(public_key.depth(), 3);
+            #[cfg(feature = "parallel")]
+            assert_eq!(
+                generator
+                    .par_public_key_only(&SEED.trits(), &nonce)
+                    .unwrap()
+                    .state,
+                expected.state
+            );
+        }
+
+        let empty: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(0, 1);
+        assert!(empty.public_key_only(&SEED.trits(), &nonce).is_err());
     }
 
     #[test]
@@ -401,10 +894,13 @@ mod should {
         let message = SEED.trits();
         let nonce = [0; 18];
         let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
-            MssPrivateKeyGenerator::from_depth(4);
+            MssPrivateKeyGenerator::from_depth(4).unwrap();
         let private_key = mss_kg.generate(&seed_trits, &nonce).unwrap();
         let signature = private_key.sign(&message).unwrap();
-        assert_eq!(signature.to_bytes().len(), 18 + 13122 + 243 * 4);
+        assert_eq!(
+            signature.to_bytes().len(),
+            MAM_MSS_SIGNATURE_HEADER_SIZE + MAM_WOTS_SIGNATURE_SIZE + 243 * 4
+        );
     }
 
     #[test]
@@ -422,7 +918,142 @@ mod should {
 
         for _ in 0..sg {
             let sig3 = private_key.sign(&message).unwrap();
-            assert_eq!(public_key.verify(&message, &sig3), true);
+            assert!(public_key.verify(&message, &sig3).is_ok());
+        }
+    }
+
+    #[test]
+    fn reject_truncated_input() {
+        let message = SEED.trits();
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(2, 1);
+        let private_key = mss_kg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let public_key = private_key.generate_public_key();
+        let signature = private_key.sign(&message).unwrap();
+        let bytes = signature.to_bytes();
+
+        let mut truncated = public_key.clone();
+        truncated.state.truncate(HASH_LEN - 1);
+        assert_eq!(
+            truncated.verify(&message, &signature),
+            Err(MssError::PublicKeyMismatch)
+        );
+        truncated.state.clear();
+        assert_eq!(
+            truncated.verify(&message, &signature),
+            Err(MssError::PublicKeyMismatch)
+        );
+        assert_eq!(
+            MssPublicKey::<MamSpongos>::from_bytes(&public_key.to_bytes()[1..]).unwrap_err(),
+            MssError::InvalidPublicKeySize(HASH_LEN - 1)
+        );
+
+        assert_eq!(
+            MssSignature::<MamSpongos>::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            MssError::InvalidSignatureSize(bytes.len() - 1)
+        );
+        let short =
+            MssSignature::<MamSpongos>::from_bytes(&bytes[..bytes.len() - HASH_LEN]).unwrap();
+        assert_eq!(
+            public_key.verify(&message, &short),
+            Err(MssError::InvalidAuthPathSize {
+                depth: 2,
+                len: HASH_LEN
+            })
+        );
+        assert!(public_key.verify(&message, &signature).is_ok());
+    }
+
+    #[test]
+    fn verify_batch_of_messages() {
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(1, 3);
+        let private_key = mss_kg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let public_key = private_key.generate_public_key();
+
+        let messages: Vec<Vec<Trit>> = (0..6)
+            .map(|i| [trits_with_length(i, 9), SEED.trits()].concat())
+            .collect();
+        let signatures: Vec<_> = messages
+            .iter()
+            .map(|m| private_key.sign(m).unwrap())
+            .collect();
+        let mut items: Vec<(&[Trit], &MssSignature<MamSpongos>)> = messages
+            .iter()
+            .map(|m| &m[..])
+            .zip(signatures.iter())
+            .collect();
+        items[3].0 = &messages[4];
+
+        let expected = vec![
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(MssError::PublicKeyMismatch),
+            Ok(()),
+            Ok(()),
+        ];
+        assert_eq!(public_key.verify_batch(&items), expected);
+        #[cfg(feature = "parallel")]
+        assert_eq!(public_key.par_verify_batch(&items), expected);
+    }
+
+    #[test]
+    fn parse_signature() {
+        let message = SEED.trits();
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(2, 1);
+        let private_key = mss_kg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        private_key.sign(&message).unwrap();
+        let signature = private_key.sign(&message).unwrap();
+
+        let parsed = signature.parse().unwrap();
+        assert_eq!(parsed.depth, 2);
+        assert_eq!(parsed.skn, 1);
+        assert_eq!(parsed.wots_signature.len(), MAM_WOTS_SIGNATURE_SIZE);
+        assert_eq!(parsed.auth_path.len(), 2);
+        assert!(parsed.auth_path.iter().all(|node| node.len() == HASH_LEN));
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes[..4].copy_from_slice(&trits_with_length(21, 4));
+        let bad_depth = MssSignature::<MamSpongos>::from_bytes(&bytes).unwrap();
+        assert_eq!(bad_depth.parse(), Err(MssError::InvalidDepth(21)));
+
+        bytes[..4].copy_from_slice(&trits_with_length(2, 4));
+        bytes[4..18].copy_from_slice(&trits_with_length(4, 14));
+        let bad_skn = MssSignature::<MamSpongos>::from_bytes(&bytes).unwrap();
+        assert_eq!(bad_skn.parse(), Err(MssError::InvalidSkn(4)));
+
+        bytes[..4].copy_from_slice(&trits_with_length(3, 4));
+        bytes[4..18].copy_from_slice(&trits_with_length(1, 14));
+        let bad_path = MssSignature::<MamSpongos>::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            bad_path.parse(),
+            Err(MssError::InvalidAuthPathSize {
+                depth: 3,
+                len: 2 * HASH_LEN
+            })
+        );
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn check_malformed_input(
+            message in prop::collection::vec(-1_i8..=1, 0..400),
+            signature in prop::collection::vec(-1_i8..=1, 0..14000),
+        ) {
+            let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+                MssPrivateKeyGenerator::new(1, 1);
+            prop_assert!(mss_kg.generate(&message, &[0; 18]).is_err() || message.len() == 243);
+
+            let private_key = mss_kg.generate(&SEED.trits(), &[0; 18]).unwrap();
+            prop_assert_eq!(private_key.sign(&message).is_ok(), message.len() >= 231);
+
+            if let Ok(signature) = MssSignature::from_bytes(&signature) {
+                let _ = private_key.generate_public_key().verify(&message, &signature);
+            }
         }
     }
 }
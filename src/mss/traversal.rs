@@ -0,0 +1,551 @@
+//!
+//! Merkle Tree Traversal
+//!
+//! The subtree traversal of FMTSEQ keeps two complete subtrees per level.
+//! The BDS traversal (Buchmann, Dahmen, Schneider) keeps the authentication
+//! path, one treehash instance per lower level and every node of the `retain`
+//! top levels:
+//!
+//! * memory: about `(d - k)(d - k + 3) / 2 + d + 2^(k + 1) - 2` hashes
+//! * time: at most `d - k` WOTS leaves per signature
+//!
+//! where `d` is the depth and `k` the number of retained levels.
+//!
+use crate::{
+    constants::trits_with_length,
+    definitions::{
+        ss::{PrivateKey, PrivateKeyGenerator, PublicKey},
+        Sponge, Trit,
+    },
+    mss::internal::{InternalPrivateKey, TreeStackItem},
+};
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use core::marker::PhantomData;
+
+///
+/// HASH LENGTH
+///
+const HASH_LEN: usize = 243;
+
+///
+/// MSS Traversal
+///
+/// How a private key computes the authentication path of its next leaf.
+/// Both produce the same signatures.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MssTraversal {
+    /// FMTSEQ subtrees, one WOTS leaf per level and signature
+    #[default]
+    Subtrees,
+    /// BDS treehash, log-space memory
+    Bds {
+        /// Top levels kept from key generation, at most the depth
+        retain: usize,
+    },
+}
+
+/// Size of the leaf index in the WOTS nonce
+const LEAF_INDEX_SIZE: usize = 6;
+/// Size of the leaf index of trees with more leaves than `LEAF_INDEX_SIZE` tells apart
+const WIDE_LEAF_INDEX_SIZE: usize = 14;
+
+///
+/// Nonce of the WOTS key of leaf `leaf`
+///
+/// 6 trits tell 729 leaves apart, later leaves take 14 trits like the skn
+/// instead of wrapping onto the WOTS keys of earlier ones.
+///
+pub(crate) fn leaf_nonce(nonce: &[Trit], leaf: usize) -> Vec<Trit> {
+    let len = if leaf < 3_usize.pow(LEAF_INDEX_SIZE as u32) {
+        LEAF_INDEX_SIZE
+    } else {
+        WIDE_LEAF_INDEX_SIZE
+    };
+    [nonce, &trits_with_length(leaf as i64, len)[..]].concat()
+}
+
+///
+/// WOTS public key of leaf `leaf`
+///
+pub(crate) fn leaf_public_key<S, G>(
+    seed: &[Trit],
+    nonce: &[Trit],
+    leaf: usize,
+) -> Result<Vec<Trit>, String>
+where
+    G: Default + PrivateKeyGenerator<S, Error = String>,
+    <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+{
+    let private_key = G::default().generate(seed, &leaf_nonce(nonce, leaf))?;
+    Ok(private_key.generate_public_key().to_bytes().to_vec())
+}
+
+///
+/// Treehash
+///
+/// Computes one node from its leaves, one leaf per update.
+///
+#[derive(Debug, Clone)]
+struct TreeHash {
+    /// Height of the computed node
+    height: usize,
+    /// Next leaf
+    next_leaf: usize,
+    /// Leaf after the last one
+    end_leaf: usize,
+    /// Partial nodes
+    stack: Vec<TreeStackItem>,
+    /// Computed node
+    node: Option<Vec<Trit>>,
+}
+
+impl TreeHash {
+    ///
+    /// Idle instance of `height`
+    ///
+    fn new(height: usize) -> Self {
+        TreeHash {
+            height,
+            next_leaf: 0,
+            end_leaf: 0,
+            stack: Vec::new(),
+            node: None,
+        }
+    }
+
+    ///
+    /// Start computing the node at `pos`
+    ///
+    fn start(&mut self, pos: usize) {
+        self.next_leaf = pos << self.height;
+        self.end_leaf = (pos + 1) << self.height;
+        self.stack.clear();
+        self.node = None;
+    }
+
+    ///
+    /// Add the next leaf
+    ///
+    fn update<S, G>(&mut self, seed: &[Trit], nonce: &[Trit]) -> Result<(), String>
+    where
+        S: Sponge<Error = String> + Default,
+        G: Default + PrivateKeyGenerator<S, Error = String>,
+        <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey,
+        <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+    {
+        if self.next_leaf >= self.end_leaf {
+            return Ok(());
+        }
+        let pk = leaf_public_key::<S, G>(seed, nonce, self.next_leaf)?;
+        let mut item = TreeStackItem::new(0, self.next_leaf, &pk);
+        self.next_leaf += 1;
+
+        let mut spongos = S::default();
+        while self.stack.last().map(|top| top.level) == Some(item.level) {
+            let left = self.stack.pop().unwrap();
+            let hash = spongos.hash(&[&left.item[..], &item.item[..]].concat(), HASH_LEN)?;
+            item = TreeStackItem::new(item.level + 1, item.pos / 2, &hash);
+        }
+
+        if item.level == self.height {
+            self.node = Some(item.item);
+        } else {
+            self.stack.push(item);
+        }
+        Ok(())
+    }
+}
+
+///
+/// Root of the subtree of `height` at `pos`
+///
+/// Leaves are streamed through a treehash stack, one node per level at most.
+///
+pub(crate) fn subtree_root<S, G>(
+    seed: &[Trit],
+    nonce: &[Trit],
+    height: usize,
+    pos: usize,
+) -> Result<Vec<Trit>, String>
+where
+    S: Sponge<Error = String> + Default,
+    G: Default + PrivateKeyGenerator<S, Error = String>,
+    <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+{
+    let mut treehash = TreeHash::new(height);
+    treehash.start(pos);
+    while treehash.node.is_none() {
+        treehash.update::<S, G>(seed, nonce)?;
+    }
+    Ok(treehash.node.unwrap())
+}
+
+///
+/// BDS Traversal
+///
+#[derive(Clone)]
+pub(crate) struct BdsTraversal<S, G> {
+    /// Seed
+    pub seed: Vec<Trit>,
+    /// Nonce
+    pub nonce: Vec<Trit>,
+    /// Tree depth
+    pub depth: usize,
+    /// Sigs used
+    pub sigs_used: usize,
+    /// Retained top levels
+    retain: usize,
+    /// Authentication path of the next leaf
+    auth: Vec<Vec<Trit>>,
+    /// Treehash instances, one per level below the retained ones
+    treehash: Vec<TreeHash>,
+    /// Nodes of the retained levels, lowest level first
+    retained: Vec<Vec<Trit>>,
+    /// Market Data
+    _sponge: PhantomData<S>,
+    _gen: PhantomData<G>,
+}
+
+impl<S, G> BdsTraversal<S, G>
+where
+    S: Sponge<Error = String> + Default,
+    G: Default + PrivateKeyGenerator<S, Error = String>,
+    <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey + Clone,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+{
+    ///
+    /// Empty traversal, filled by `store` during key generation
+    ///
+    pub fn new(seed: &[Trit], nonce: &[Trit], depth: usize, retain: usize) -> Self {
+        BdsTraversal {
+            seed: seed.to_vec(),
+            nonce: nonce.to_vec(),
+            depth,
+            sigs_used: 0,
+            retain,
+            auth: vec![Vec::new(); depth],
+            treehash: (0..depth - retain).map(TreeHash::new).collect(),
+            retained: (depth - retain..depth)
+                .map(|level| vec![0i8; (1 << (depth - level)) * HASH_LEN])
+                .collect(),
+            _sponge: PhantomData,
+            _gen: PhantomData,
+        }
+    }
+
+    ///
+    /// Keep a node of the key generation
+    ///
+    /// Leaf 0 needs the right siblings on its path, the treehash instances
+    /// start with the left ones, which leaf `2^level` needs first.
+    ///
+    pub(crate) fn store(&mut self, item: &TreeStackItem) {
+        if item.level >= self.depth {
+            // root
+            return;
+        }
+        if item.pos == 1 {
+            self.auth[item.level] = item.item.clone();
+        }
+        let first_retained = self.depth - self.retain;
+        if item.level >= first_retained {
+            let nodes = &mut self.retained[item.level - first_retained];
+            nodes[(item.pos * HASH_LEN)..((item.pos + 1) * HASH_LEN)].copy_from_slice(&item.item);
+        } else if item.pos == 0 {
+            self.treehash[item.level].node = Some(item.item.clone());
+        }
+    }
+
+    ///
+    /// Check Private Key
+    ///
+    pub(crate) fn check_privkey(&self) -> bool {
+        self.sigs_used < (1 << self.depth)
+            && self.auth.len() == self.depth
+            && self.auth.iter().all(|node| node.len() == HASH_LEN)
+    }
+
+    ///
+    /// Move to the next leaf
+    ///
+    /// Every running treehash adds one leaf, then the levels whose sibling
+    /// changes take the node of their treehash, or of the retained levels.
+    ///
+    pub(crate) fn update_private_key(&mut self) -> Result<(), String> {
+        for treehash in self.treehash.iter_mut() {
+            treehash.update::<S, G>(&self.seed, &self.nonce)?;
+        }
+
+        let next = self.sigs_used + 1;
+        self.sigs_used = next;
+        if next >= 1 << self.depth {
+            return Ok(());
+        }
+
+        let first_retained = self.depth - self.retain;
+        for level in 0..self.depth {
+            if !next.is_multiple_of(1 << level) {
+                break;
+            }
+            let pos = (next >> level) ^ 1;
+            if level >= first_retained {
+                let nodes = &self.retained[level - first_retained];
+                self.auth[level] = nodes[(pos * HASH_LEN)..((pos + 1) * HASH_LEN)].to_vec();
+                continue;
+            }
+
+            let treehash = &mut self.treehash[level];
+            // a running treehash gets exactly 2^level updates before its node is due
+            while treehash.node.is_none() && treehash.next_leaf < treehash.end_leaf {
+                treehash.update::<S, G>(&self.seed, &self.nonce)?;
+            }
+            self.auth[level] = treehash
+                .node
+                .take()
+                .ok_or_else(|| "MSS treehash has no node for the next leaf".to_owned())?;
+            let following = (next >> level) + 1;
+            if following < 1 << (self.depth - level) {
+                treehash.start(following ^ 1);
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Retrieve the Authentication Path
+    ///
+    pub fn apath(&self) -> Vec<Trit> {
+        self.auth.concat()
+    }
+
+    ///
+    /// SKN
+    ///
+    pub fn skn(&self) -> [i8; 18] {
+        let mut encoded_skn = [0i8; 18];
+        let t_depth = trits_with_length(self.depth as i64, 4);
+        let t_skn = trits_with_length(self.sigs_used as i64, 14);
+
+        encoded_skn[..4].copy_from_slice(&t_depth[..]);
+        encoded_skn[4..].copy_from_slice(&t_skn[..]);
+        encoded_skn
+    }
+}
+
+///
+/// Traversal state of a private key
+///
+#[derive(Clone)]
+pub(crate) enum Traversal<S, G> {
+    /// FMTSEQ subtrees
+    Subtrees(InternalPrivateKey<S, G>),
+    /// BDS treehash
+    Bds(BdsTraversal<S, G>),
+}
+
+impl<S, G> Traversal<S, G>
+where
+    S: Sponge<Error = String> + Default,
+    G: Default + PrivateKeyGenerator<S, Error = String>,
+    <G as PrivateKeyGenerator<S>>::PrivateKey: PrivateKey + Clone,
+    <<G as PrivateKeyGenerator<S>>::PrivateKey as PrivateKey>::PublicKey: PublicKey,
+{
+    ///
+    /// Seed
+    ///
+    pub(crate) fn seed(&self) -> &[Trit] {
+        match self {
+            Traversal::Subtrees(mt) => &mt.seed,
+            Traversal::Bds(mt) => &mt.seed,
+        }
+    }
+
+    ///
+    /// Nonce
+    ///
+    pub(crate) fn nonce(&self) -> &[Trit] {
+        match self {
+            Traversal::Subtrees(mt) => &mt.nonce,
+            Traversal::Bds(mt) => &mt.nonce,
+        }
+    }
+
+    ///
+    /// Tree depth
+    ///
+    pub(crate) fn depth(&self) -> usize {
+        match self {
+            Traversal::Subtrees(mt) => mt.depth(),
+            Traversal::Bds(mt) => mt.depth,
+        }
+    }
+
+    ///
+    /// Sigs used
+    ///
+    pub(crate) fn sigs_used(&self) -> usize {
+        match self {
+            Traversal::Subtrees(mt) => mt.sigs_used,
+            Traversal::Bds(mt) => mt.sigs_used,
+        }
+    }
+
+    ///
+    /// Check Private Key
+    ///
+    pub(crate) fn check_privkey(&self) -> bool {
+        match self {
+            Traversal::Subtrees(mt) => mt.check_privkey(),
+            Traversal::Bds(mt) => mt.check_privkey(),
+        }
+    }
+
+    ///
+    /// Keep a node of the key generation
+    ///
+    pub(crate) fn store(&mut self, item: &TreeStackItem) {
+        match self {
+            Traversal::Subtrees(mt) => mt.store_exist(item),
+            Traversal::Bds(mt) => mt.store(item),
+        }
+    }
+
+    ///
+    /// Retrieve the Authentication Path
+    ///
+    pub(crate) fn apath(&self) -> Vec<Trit> {
+        match self {
+            Traversal::Subtrees(mt) => mt.apath(),
+            Traversal::Bds(mt) => mt.apath(),
+        }
+    }
+
+    ///
+    /// SKN
+    ///
+    pub(crate) fn skn(&self) -> [i8; 18] {
+        match self {
+            Traversal::Subtrees(mt) => mt.skn(),
+            Traversal::Bds(mt) => mt.skn(),
+        }
+    }
+
+    ///
+    /// Sigs Remaning
+    ///
+    pub(crate) fn sigs_remaining(&self) -> usize {
+        match self {
+            Traversal::Subtrees(mt) => mt.sigs_remaining(),
+            Traversal::Bds(mt) => (1 << mt.depth) - mt.sigs_used,
+        }
+    }
+
+    ///
+    /// Move to the next leaf
+    ///
+    pub(crate) fn update_private_key(&mut self) -> Result<(), String> {
+        match self {
+            Traversal::Subtrees(mt) => {
+                mt.update_private_key();
+                Ok(())
+            }
+            Traversal::Bds(mt) => mt.update_private_key(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{
+        definitions::ss::Signature,
+        mss::{MssError, MssPrivateKeyGenerator},
+        spongos::MamSpongos,
+        wots::WotsPrivateKeyGenerator,
+    };
+    use iota_conversion::Trinary;
+
+    type Generator = MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>>;
+
+    const SEED: &str =
+        "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
+
+    #[test]
+    fn match_subtree_paths() {
+        let message = SEED.trits();
+        let nonce = [0; 18];
+        for &(height, level, retain) in [(2, 2, 1), (1, 3, 0), (3, 1, 3)].iter() {
+            let subtrees = Generator::new(height, level)
+                .generate(&SEED.trits(), &nonce)
+                .unwrap();
+            let bds = Generator::with_traversal(height, level, MssTraversal::Bds { retain })
+                .generate(&SEED.trits(), &nonce)
+                .unwrap();
+            assert_eq!(
+                subtrees.generate_public_key().state,
+                bds.generate_public_key().state
+            );
+
+            for _ in 0..(1 << (height * level)) - 1 {
+                assert_eq!(subtrees.i_mt.borrow().apath(), bds.i_mt.borrow().apath());
+                assert_eq!(
+                    subtrees.sign(&message).unwrap().to_bytes(),
+                    bds.sign(&message).unwrap().to_bytes()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn separate_leaf_nonces() {
+        assert_eq!(
+            leaf_nonce(&[1], 728),
+            [&[1], &trits_with_length(728, 6)[..]].concat()
+        );
+        assert_eq!(leaf_nonce(&[1], 729).len(), 1 + WIDE_LEAF_INDEX_SIZE);
+
+        let mut nonces: Vec<Vec<Trit>> = (0..3 * 729).map(|leaf| leaf_nonce(&[], leaf)).collect();
+        nonces.sort();
+        nonces.dedup();
+        assert_eq!(nonces.len(), 3 * 729);
+    }
+
+    #[test]
+    fn separate_leaf_keys() {
+        type Wots = WotsPrivateKeyGenerator<MamSpongos>;
+        let nonce = [0; 18];
+        let first = leaf_public_key::<MamSpongos, Wots>(&SEED.trits(), &nonce, 0).unwrap();
+        let wrapped = leaf_public_key::<MamSpongos, Wots>(&SEED.trits(), &nonce, 729).unwrap();
+        assert_ne!(first, wrapped);
+    }
+
+    #[test]
+    fn sign_every_leaf() {
+        let message = SEED.trits();
+        let private_key = Generator::with_traversal(1, 3, MssTraversal::Bds { retain: 1 })
+            .generate(&SEED.trits(), &[0; 18])
+            .unwrap();
+        let public_key = private_key.generate_public_key();
+
+        for remaining in (1..=8).rev() {
+            assert_eq!(private_key.sigs_remaining(), remaining);
+            let signature = private_key.sign(&message).unwrap();
+            assert!(public_key.verify(&message, &signature).is_ok());
+        }
+        assert_eq!(private_key.sigs_remaining(), 0);
+        assert_eq!(
+            private_key.sign(&message).unwrap_err(),
+            MssError::KeyExhausted
+        );
+    }
+
+    #[test]
+    fn reject_retaining_above_depth() {
+        let generator = Generator::with_traversal(2, 2, MssTraversal::Bds { retain: 5 });
+        assert_eq!(generator.traversal(), MssTraversal::Bds { retain: 5 });
+        assert!(generator.generate(&SEED.trits(), &[0; 18]).is_err());
+    }
+}
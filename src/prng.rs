@@ -5,11 +5,12 @@
 //!
 
 use crate::{
-    definitions::Sponge,
-    sponge::{MamSponge, SpongeCtrl},
+    constants::{put_sized, MAM_SIZE_FIELD_LEN},
+    definitions::{check_trits, Trit},
+    sponge::{MamSponge, SpongeCtrl, MAM_SPONGE_RATE},
 };
-use iota_conversion::Trit;
-use std::fmt;
+use alloc::{borrow::ToOwned, format, string::String, vec, vec::Vec};
+use core::fmt;
 
 /// PRNG Secret Key Size
 ///
@@ -17,6 +18,7 @@ pub const MAM_PRNG_SECRET_KEY_SIZE: usize = 243;
 
 /// PRNG Destination Tryte
 ///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PrngDestinationTryte {
     /// DstSecKey
     DstSecKey = 0,
@@ -24,6 +26,8 @@ pub enum PrngDestinationTryte {
     DstWotsKey = 1,
     /// DstNtruKey
     DstNtruKey = 2,
+    /// DstCustom, application outputs derived by `Prng::gen_labelled`
+    DstCustom = 3,
 }
 
 impl PrngDestinationTryte {
@@ -33,6 +37,7 @@ impl PrngDestinationTryte {
             PrngDestinationTryte::DstSecKey => [0, 0, 0],
             PrngDestinationTryte::DstWotsKey => [1, 0, 0],
             PrngDestinationTryte::DstNtruKey => [-1, 1, 0],
+            PrngDestinationTryte::DstCustom => [0, 1, 0],
         }
     }
 }
@@ -55,10 +60,19 @@ impl Prng {
     ///
     /// New instance
     ///
-    pub fn new(secret_key: &[Trit]) -> Self {
+    /// * `secret_key` - `MAM_PRNG_SECRET_KEY_SIZE` trits
+    ///
+    pub fn new(secret_key: &[Trit]) -> Result<Self, String> {
+        if secret_key.len() != MAM_PRNG_SECRET_KEY_SIZE {
+            return Err(format!(
+                "PRNG secret key must be {} trits long",
+                MAM_PRNG_SECRET_KEY_SIZE
+            ));
+        }
+        check_trits(secret_key)?;
         let mut sk = [0i8; MAM_PRNG_SECRET_KEY_SIZE];
-        sk[0..MAM_PRNG_SECRET_KEY_SIZE].copy_from_slice(secret_key);
-        Prng { secret_key: sk }
+        sk.copy_from_slice(secret_key);
+        Ok(Prng { secret_key: sk })
     }
     ///
     ///  Generate pseudoreandom numbers
@@ -69,25 +83,156 @@ impl Prng {
         nonce: &[Trit],
         n: usize,
     ) -> Result<Vec<Trit>, String> {
+        let mut output = vec![0; n];
+        self.fill(destination, nonce, &mut output)?;
+        Ok(output)
+    }
+
+    ///
+    /// Generate pseudorandom numbers into `output`, the same as `gen` of its length
+    ///
+    pub fn fill(
+        &mut self,
+        destination: PrngDestinationTryte,
+        nonce: &[Trit],
+        output: &mut [Trit],
+    ) -> Result<(), String> {
+        let mut spg = self.keyed_sponge(destination, nonce)?;
+        spg.squeeze_into(SpongeCtrl::Prn, output)
+    }
+
+    ///
+    /// Unbounded stream of pseudorandom numbers
+    ///
+    /// The stream never squeezes a last block, so it only matches `gen` up to
+    /// the last block of the `gen` output.
+    ///
+    pub fn stream(
+        &self,
+        destination: PrngDestinationTryte,
+        nonce: &[Trit],
+    ) -> Result<PrngStream, String> {
+        Ok(PrngStream {
+            sponge: self.keyed_sponge(destination, nonce)?,
+            block: [0; MAM_SPONGE_RATE],
+            pos: MAM_SPONGE_RATE,
+        })
+    }
+
+    ///
+    /// Sponge keyed with the secret key, `destination` and `nonce`
+    ///
+    /// The key is streamed instead of concatenated, but not cached: it fills
+    /// half of the first block, which only runs through the transform once
+    /// the destination and the nonce are in. Absorbing the key on its own
+    /// would change every output.
+    ///
+    fn keyed_sponge(
+        &self,
+        destination: PrngDestinationTryte,
+        nonce: &[Trit],
+    ) -> Result<MamSponge, String> {
         let mut spg = MamSponge::default();
-        let data = [&self.secret_key, &destination.trits()[..], &nonce[..]].concat();
-        spg.absorb((SpongeCtrl::Key, data))?;
-        Ok(spg.squeeze((SpongeCtrl::Prn, n)))
+        let mut absorber = spg.absorber(SpongeCtrl::Key)?;
+        absorber.update(&self.secret_key)?;
+        absorber.update(&destination.trits())?;
+        absorber.update(nonce)?;
+        absorber.finalize();
+        Ok(spg)
+    }
+
+    ///
+    /// Generate pseudorandom numbers for an application defined `label`
+    ///
+    /// Outputs live under the `DstCustom` destination, so they never collide
+    /// with MAM keys. The label is length prefixed, two labels only give the
+    /// same output if they are equal.
+    ///
+    pub fn gen_labelled(
+        &mut self,
+        label: &[Trit],
+        nonce: &[Trit],
+        n: usize,
+    ) -> Result<Vec<Trit>, String> {
+        check_trits(label)?;
+        if label.is_empty() {
+            return Err("PRNG label must not be empty".to_owned());
+        }
+        let mut labelled_nonce = Vec::with_capacity(MAM_SIZE_FIELD_LEN + label.len() + nonce.len());
+        put_sized(&mut labelled_nonce, label);
+        labelled_nonce.extend_from_slice(nonce);
+        self.gen(PrngDestinationTryte::DstCustom, &labelled_nonce, n)
+    }
+}
+
+///
+/// PRNG Stream
+///
+/// Iterator over an unbounded sequence of pseudorandom trits.
+///
+pub struct PrngStream {
+    /// Keyed sponge
+    sponge: MamSponge,
+    /// Current block
+    block: [Trit; MAM_SPONGE_RATE],
+    /// Position of the next trit in `block`
+    pos: usize,
+}
+
+impl PrngStream {
+    ///
+    /// Fill `output` with the next trits of the stream
+    ///
+    pub fn fill(&mut self, output: &mut [Trit]) {
+        let mut output = output;
+        while !output.is_empty() {
+            if self.pos == MAM_SPONGE_RATE {
+                self.sponge
+                    .squeeze_block(SpongeCtrl::Prn.ctrl(), &mut self.block, false);
+                self.pos = 0;
+            }
+            let n = output.len().min(MAM_SPONGE_RATE - self.pos);
+            output[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+            self.pos += n;
+            output = &mut output[n..];
+        }
+    }
+}
+
+impl Iterator for PrngStream {
+    type Item = Trit;
+
+    fn next(&mut self) -> Option<Trit> {
+        let mut trit = [0];
+        self.fill(&mut trit);
+        Some(trit[0])
+    }
+}
+
+impl fmt::Debug for PrngStream {
+    /// Format, without the keyed state
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PrngStream")
     }
 }
 
 #[cfg(test)]
 mod should {
+    use super::{
+        Prng, PrngDestinationTryte, SpongeCtrl, MAM_PRNG_SECRET_KEY_SIZE, MAM_SPONGE_RATE,
+    };
+    use alloc::{vec, vec::Vec};
+    use proptest::prelude::*;
+
     #[test]
     fn test_prng() {
-        use super::{Prng, PrngDestinationTryte, MAM_PRNG_SECRET_KEY_SIZE};
         use iota_conversion::Trinary;
         const KEY_TRYTES: &str =
             "NOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLM";
 
         let k = KEY_TRYTES.trits();
         let n = [0i8; 18];
-        let mut prng = Prng::new(&k);
+        let mut prng = Prng::new(&k).unwrap();
         let y1 = prng
             .gen(
                 PrngDestinationTryte::DstSecKey,
@@ -105,4 +250,109 @@ mod should {
 
         assert_ne!(y1, y2)
     }
+
+    #[test]
+    fn fill_and_stream() {
+        use iota_conversion::Trinary;
+
+        let mut prng = Prng::new(&[1; MAM_PRNG_SECRET_KEY_SIZE]).unwrap();
+        let nonce = "NONCE".trits();
+        let expected = prng
+            .gen(PrngDestinationTryte::DstWotsKey, &nonce, 13122)
+            .unwrap();
+
+        let mut filled = [0; 13122];
+        prng.fill(PrngDestinationTryte::DstWotsKey, &nonce, &mut filled)
+            .unwrap();
+        assert_eq!(&filled[..], &expected[..]);
+
+        // every block but the last one is shared with `gen`
+        let shared = 13122 / MAM_SPONGE_RATE * MAM_SPONGE_RATE - MAM_SPONGE_RATE;
+        let mut stream = prng
+            .stream(PrngDestinationTryte::DstWotsKey, &nonce)
+            .unwrap();
+        let mut head = vec![0; 100];
+        stream.fill(&mut head);
+        let rest: Vec<_> = stream.by_ref().take(shared - 100).collect();
+        assert_eq!([head, rest].concat(), &expected[..shared]);
+        assert_eq!(stream.take(5000).count(), 5000);
+    }
+
+    #[test]
+    fn match_concatenated_key() {
+        use crate::sponge::MamSponge;
+        use iota_conversion::Trinary;
+
+        let key = [1; MAM_PRNG_SECRET_KEY_SIZE];
+        let nonce = "NONCE".trits();
+        let mut sponge = MamSponge::default();
+        sponge
+            .absorb_trits(
+                SpongeCtrl::Key,
+                &[&key[..], &PrngDestinationTryte::DstNtruKey.trits(), &nonce].concat(),
+            )
+            .unwrap();
+        let mut expected = [0; 1000];
+        sponge.squeeze_into(SpongeCtrl::Prn, &mut expected).unwrap();
+
+        let mut prng = Prng::new(&key).unwrap();
+        assert_eq!(
+            prng.gen(PrngDestinationTryte::DstNtruKey, &nonce, 1000)
+                .unwrap(),
+            &expected[..]
+        );
+    }
+
+    #[test]
+    fn separate_destinations() {
+        use iota_conversion::Trinary;
+
+        let mut prng = Prng::new(&[1; MAM_PRNG_SECRET_KEY_SIZE]).unwrap();
+        let nonce = "NONCE".trits();
+        let outputs = [
+            prng.gen(PrngDestinationTryte::DstSecKey, &nonce, 243)
+                .unwrap(),
+            prng.gen(PrngDestinationTryte::DstWotsKey, &nonce, 243)
+                .unwrap(),
+            prng.gen(PrngDestinationTryte::DstNtruKey, &nonce, 243)
+                .unwrap(),
+            prng.gen(PrngDestinationTryte::DstCustom, &nonce, 243)
+                .unwrap(),
+            prng.gen_labelled(&"APP".trits(), &nonce, 243).unwrap(),
+            prng.gen_labelled(&"APQ".trits(), &nonce, 243).unwrap(),
+            // the label does not run into the nonce
+            prng.gen_labelled(&"APPN".trits(), &"ONCE".trits(), 243)
+                .unwrap(),
+        ];
+        for (i, a) in outputs.iter().enumerate() {
+            for b in outputs[i + 1..].iter() {
+                assert_ne!(a, b);
+            }
+        }
+
+        assert_eq!(
+            prng.gen_labelled(&"APP".trits(), &nonce, 243).unwrap(),
+            outputs[4]
+        );
+        assert!(prng.gen_labelled(&[], &nonce, 243).is_err());
+        assert!(prng.gen_labelled(&[3], &nonce, 243).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn check_key_length(
+            key in prop::collection::vec(-1_i8..=1, 0..500),
+            nonce in prop::collection::vec(-1_i8..=1, 0..100),
+            n in 0_usize..1000,
+        ) {
+            match Prng::new(&key) {
+                Ok(mut prng) => {
+                    prop_assert_eq!(key.len(), MAM_PRNG_SECRET_KEY_SIZE);
+                    let prn = prng.gen(PrngDestinationTryte::DstSecKey, &nonce, n).unwrap();
+                    prop_assert_eq!(prn.len(), n);
+                }
+                Err(_) => prop_assert_ne!(key.len(), MAM_PRNG_SECRET_KEY_SIZE),
+            }
+        }
+    }
 }
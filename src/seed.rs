@@ -0,0 +1,149 @@
+//! Seed Generation
+//!
+//! Random seeds for `Prng::new`, `KeyHierarchy::new` and the private key
+//! generators. Every random byte below 243 is turned into 5 uniform trits,
+//! larger bytes are rejected so that no trit value is favoured.
+//!
+
+use crate::{definitions::Trit, prng::MAM_PRNG_SECRET_KEY_SIZE};
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+/// Size of a seed
+pub const MAM_SEED_SIZE: usize = MAM_PRNG_SECRET_KEY_SIZE;
+/// Number of batches read before giving up on a source that only yields rejected bytes
+const MAX_BATCHES: usize = 64;
+/// Random bytes read at once
+const BATCH_LEN: usize = 64;
+
+///
+/// Source of uniformly random bytes
+///
+/// Tests inject a deterministic source, applications use `OsEntropy`.
+///
+pub trait EntropySource {
+    ///
+    /// Fill `bytes` with random bytes
+    ///
+    fn fill_bytes(&mut self, bytes: &mut [u8]) -> Result<(), String>;
+}
+
+///
+/// Random number generator of the operating system
+///
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsEntropy;
+
+#[cfg(feature = "std")]
+impl EntropySource for OsEntropy {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) -> Result<(), String> {
+        getrandom::fill(bytes).map_err(|e| e.to_string())
+    }
+}
+
+///
+/// Generate a `MAM_SEED_SIZE` trits seed from the OS random number generator
+///
+#[cfg(feature = "std")]
+pub fn generate_seed() -> Result<Vec<Trit>, String> {
+    generate_seed_with(&mut OsEntropy)
+}
+
+///
+/// Generate a `MAM_SEED_SIZE` trits seed from `source`
+///
+pub fn generate_seed_with<R>(source: &mut R) -> Result<Vec<Trit>, String>
+where
+    R: EntropySource,
+{
+    let mut seed = Vec::with_capacity(MAM_SEED_SIZE + 5);
+    let mut bytes = [0_u8; BATCH_LEN];
+
+    for _ in 0..MAX_BATCHES {
+        source.fill_bytes(&mut bytes)?;
+        for byte in bytes.iter().filter(|byte| **byte < 243) {
+            let mut value = *byte;
+            for _ in 0..5 {
+                seed.push((value % 3) as Trit - 1);
+                value /= 3;
+            }
+            if seed.len() >= MAM_SEED_SIZE {
+                seed.truncate(MAM_SEED_SIZE);
+                return Ok(seed);
+            }
+        }
+    }
+    Err("Entropy source keeps returning rejected bytes".to_owned())
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    /// Counter source, every byte value in turn
+    struct Counter(u8);
+
+    impl EntropySource for Counter {
+        fn fill_bytes(&mut self, bytes: &mut [u8]) -> Result<(), String> {
+            for byte in bytes.iter_mut() {
+                *byte = self.0;
+                self.0 = self.0.wrapping_add(1);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn skip_rejected_bytes() {
+        let seed = generate_seed_with(&mut Counter(0)).unwrap();
+        assert_eq!(seed.len(), MAM_SEED_SIZE);
+        // byte 0, then byte 1, least significant trit first
+        assert_eq!(seed[..10], [-1, -1, -1, -1, -1, 0, -1, -1, -1, -1]);
+        // byte 48 = 0 + 1 * 3 + 2 * 9 + 1 * 27, only 3 of its trits fit
+        assert_eq!(seed[240..], [-1, 0, 1]);
+
+        // bytes 243..=255 come first and are skipped
+        assert_eq!(generate_seed_with(&mut Counter(243)).unwrap(), seed);
+    }
+
+    #[test]
+    fn reject_biased_source() {
+        struct Stuck;
+        impl EntropySource for Stuck {
+            fn fill_bytes(&mut self, bytes: &mut [u8]) -> Result<(), String> {
+                bytes.iter_mut().for_each(|byte| *byte = 250);
+                Ok(())
+            }
+        }
+        assert!(generate_seed_with(&mut Stuck).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn seed_key_generators() {
+        use crate::{
+            definitions::ss::{PrivateKey, PrivateKeyGenerator},
+            prng::{Prng, PrngDestinationTryte},
+            spongos::MamSpongos,
+            wots::WotsPrivateKeyGenerator,
+        };
+
+        let seed = generate_seed().unwrap();
+        assert_eq!(seed.len(), MAM_SEED_SIZE);
+        assert_ne!(seed, generate_seed().unwrap());
+
+        let mut prng = Prng::new(&seed).unwrap();
+        assert!(prng
+            .gen(PrngDestinationTryte::DstSecKey, &[0; 18], 81)
+            .is_ok());
+        let wkg: WotsPrivateKeyGenerator<MamSpongos> = WotsPrivateKeyGenerator::default();
+        let private_key = wkg.generate(&seed, &[0; 18]).unwrap();
+        let signature = private_key.sign_message(&seed).unwrap();
+        assert!(private_key
+            .generate_public_key()
+            .verify_message(&seed, &signature)
+            .is_ok());
+    }
+}
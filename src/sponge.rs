@@ -1,9 +1,17 @@
 //! MAM Sponge Layer
 
-use crate::definitions::{Sponge, Transform};
-use iota_conversion::Trit;
-use std::fmt;
-use troika::Ftroika;
+use crate::{
+    constants::{put_int, take, take_int},
+    definitions::{check_trits, Sponge, Transform, Trit},
+    troika::Ftroika,
+};
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
 
 /// Sponge state rate
 pub const MAM_SPONGE_RATE: usize = 486;
@@ -12,7 +20,7 @@ pub const MAM_SPONGE_CONTROL: usize = 6;
 /// Sponge state capacity
 pub const MAM_SPONGE_CAPACITY: usize = 237;
 /// Sponge state width
-pub const MAM_SPONGE_WIDTH: usize = (MAM_SPONGE_RATE + MAM_SPONGE_CONTROL + MAM_SPONGE_CAPACITY);
+pub const MAM_SPONGE_WIDTH: usize = MAM_SPONGE_RATE + MAM_SPONGE_CONTROL + MAM_SPONGE_CAPACITY;
 
 /// Sponge fixed key size
 pub const MAM_SPONGE_KEY_SIZE: usize = 243;
@@ -21,6 +29,13 @@ pub const MAM_SPONGE_HASH_SIZE: usize = 243;
 /// Sponge fixed MAC size
 pub const MAM_SPONGE_MAC_SIZE: usize = 243;
 
+/// Size of the checksum closing a state snapshot
+pub const MAM_SNAPSHOT_CHECKSUM_SIZE: usize = 81;
+/// Version of the state snapshot format
+const SNAPSHOT_VERSION: i64 = 1;
+/// Length of the version field of a state snapshot
+const SNAPSHOT_VERSION_LEN: usize = 3;
+
 /// MAM Sponge CTRL
 pub enum SpongeCtrl {
     /// Control trit DATA
@@ -79,11 +94,16 @@ pub struct SpongeTransform;
 
 impl Transform for SpongeTransform {
     fn transform(state: &mut [Trit]) {
-        let mut fstate = state.iter().map(|t| (*t + 1) as u8).collect::<Vec<u8>>();
+        let mut buffer = [0u8; MAM_SPONGE_WIDTH];
+        let fstate = &mut buffer[..state.len()];
+        fstate
+            .iter_mut()
+            .zip(state.iter())
+            .for_each(|(f, t)| *f = (*t + 1) as u8);
 
         let mut ftroika = Ftroika::default();
-        ftroika.absorb(&fstate);
-        ftroika.squeeze(&mut fstate);
+        ftroika.absorb(fstate);
+        ftroika.squeeze(fstate);
 
         fstate.iter().enumerate().for_each(|(idx, t)| {
             let v = *t as i8;
@@ -108,178 +128,498 @@ impl MamSponge {
     pub fn transform(&mut self) {
         SpongeTransform::transform(&mut self.state);
     }
-}
 
-impl Sponge for MamSponge {
-    type Error = String;
-    type AbsorbInput = (SpongeCtrl, Vec<Trit>);
-    type SqueezeInput = (SpongeCtrl, usize);
+    ///
+    /// Sponge absorption of a slice of trits
+    ///
+    /// * `c2` - Control, `SpongeCtrl::Data` or `SpongeCtrl::Key`
+    /// * `data` - Input data
+    ///
+    pub fn absorb_trits(&mut self, c2: SpongeCtrl, data: &[Trit]) -> Result<(), String> {
+        if !matches!(c2, SpongeCtrl::Data | SpongeCtrl::Key) {
+            return Err(
+                "Control Trits must be MAM_SPONGE_CTL_DATA or MAM_SPONGE_CTL_KEY".to_string(),
+            );
+        }
 
-    fn absorb(&mut self, absorb_info: Self::AbsorbInput) -> Result<(), Self::Error> {
-        let c2 = absorb_info.0;
-        let chk = match c2 {
-            SpongeCtrl::Data => false,
-            SpongeCtrl::Key => false,
-            _ => true,
-        };
-        if chk {
-            return Err(format!(
-                "Control Trits must be MAM_SPONGE_CTL_DATA or MAM_SPONGE_CTL_KEY"
-            ));
+        check_trits(data)?;
+
+        let r_data: &[Trit] = if data.is_empty() { &[0] } else { data };
+
+        let n: usize = r_data.len().div_ceil(MAM_SPONGE_RATE);
+        for (idx, chunk) in r_data.chunks(MAM_SPONGE_RATE).enumerate() {
+            self.absorb_block(c2.ctrl(), chunk, idx == n - 1);
         }
 
-        let r_data = if absorb_info.1.len() == 0 {
-            [0i8].to_vec()
+        Ok(())
+    }
+
+    ///
+    /// Sponge squeezing into a buffer
+    ///
+    /// * `ctrl` - Control, `SpongeCtrl::Hash`, `SpongeCtrl::Prn` or `SpongeCtrl::Mac`
+    /// * `squeezed` - Output buffer, its length is the output length
+    ///
+    pub fn squeeze_into(&mut self, ctrl: SpongeCtrl, squeezed: &mut [Trit]) -> Result<(), String> {
+        if !matches!(ctrl, SpongeCtrl::Hash | SpongeCtrl::Prn | SpongeCtrl::Mac) {
+            return Err(
+                "Control Trits must be MAM_SPONGE_CTL_HASH, MAM_SPONGE_CTL_PRN or MAM_SPONGE_CTL_MAC"
+                    .to_string(),
+            );
+        }
+
+        let n: usize = squeezed.len().div_ceil(MAM_SPONGE_RATE);
+
+        for (idx, chunk) in squeezed.chunks_mut(MAM_SPONGE_RATE).enumerate() {
+            self.squeeze_block(ctrl.ctrl(), chunk, idx == n - 1);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Squeeze one block of at most `MAM_SPONGE_RATE` trits
+    ///
+    pub(crate) fn squeeze_block(&mut self, c2: Trit, chunk: &mut [Trit], last: bool) {
+        let t0: Trit = -1;
+        let t1: Trit = if last { -1 } else { 1 };
+
+        self.state[489..492].copy_from_slice(&[t0, t1, c2]);
+
+        SpongeTransform::transform(&mut self.state);
+
+        chunk.copy_from_slice(&self.state[..chunk.len()]);
+
+        if chunk.len() == MAM_SPONGE_RATE {
+            self.state[..MAM_SPONGE_RATE].copy_from_slice(&[0; MAM_SPONGE_RATE]);
         } else {
-            absorb_info.1
-        };
+            let mut padr: [Trit; MAM_SPONGE_RATE] = [0; MAM_SPONGE_RATE];
+            padr[chunk.len() - 1] = 1;
+            self.state[..MAM_SPONGE_RATE].copy_from_slice(&padr);
+        }
+        self.state[MAM_SPONGE_RATE..489].copy_from_slice(&[t0, t1, c2]);
+    }
+
+    ///
+    /// Snapshot of the state
+    ///
+    /// Versioned and closed by a checksum, restore it with `from_trits`.
+    ///
+    pub fn to_trits(&self) -> Vec<Trit> {
+        let mut out = new_snapshot();
+        out.extend_from_slice(&self.state);
+        seal_snapshot(out)
+    }
+
+    ///
+    /// Restore a snapshot produced by `to_trits`
+    ///
+    pub fn from_trits(trits: &[Trit]) -> Result<Self, String> {
+        let mut input = open_snapshot(trits)?;
+        let sponge = MamSponge::take_snapshot_state(&mut input)?;
+        if !input.is_empty() {
+            return Err("Trailing trits after sponge state".to_owned());
+        }
+        Ok(sponge)
+    }
+
+    ///
+    /// Take a sponge state from the front of `input`
+    ///
+    pub(crate) fn take_snapshot_state(input: &mut &[Trit]) -> Result<Self, String> {
+        let trits = take(input, MAM_SPONGE_WIDTH)?;
+        check_trits(trits)?;
+        let mut sponge = MamSponge::default();
+        sponge.state.copy_from_slice(trits);
+        Ok(sponge)
+    }
+
+    ///
+    /// Streaming absorption, see `SpongeAbsorber`
+    ///
+    /// * `c2` - Control, `SpongeCtrl::Data` or `SpongeCtrl::Key`
+    ///
+    pub fn absorber(&mut self, c2: SpongeCtrl) -> Result<SpongeAbsorber<'_>, String> {
+        if !matches!(c2, SpongeCtrl::Data | SpongeCtrl::Key) {
+            return Err(
+                "Control Trits must be MAM_SPONGE_CTL_DATA or MAM_SPONGE_CTL_KEY".to_string(),
+            );
+        }
+
+        Ok(SpongeAbsorber {
+            sponge: self,
+            ctrl: c2.ctrl(),
+            buffer: BlockBuffer::default(),
+        })
+    }
+
+    ///
+    /// Streaming encryption, see `SpongeCipher`
+    ///
+    pub fn encryptor(&mut self) -> SpongeCipher<'_> {
+        SpongeCipher {
+            sponge: self,
+            decrypt: false,
+            buffer: BlockBuffer::default(),
+        }
+    }
+
+    ///
+    /// Streaming decryption, see `SpongeCipher`
+    ///
+    pub fn decryptor(&mut self) -> SpongeCipher<'_> {
+        SpongeCipher {
+            sponge: self,
+            decrypt: true,
+            buffer: BlockBuffer::default(),
+        }
+    }
+
+    ///
+    /// Absorb one block of at most `MAM_SPONGE_RATE` trits
+    ///
+    fn absorb_block(&mut self, c2: Trit, chunk: &[Trit], last: bool) {
+        let c0 = if chunk.len() == MAM_SPONGE_RATE { 1 } else { 0 };
+        let c1 = if last { -1 } else { 1 };
+
+        if self.state[MAM_SPONGE_RATE + 1] != 0 {
+            self.state[489..492].copy_from_slice(&[c0, c1, c2]);
+            SpongeTransform::transform(&mut self.state);
+        }
+
+        let mut padr = [0; MAM_SPONGE_RATE + 1];
+        padr[..chunk.len()].copy_from_slice(chunk);
+        padr[chunk.len()] = 1;
+
+        self.state[..487].copy_from_slice(&padr);
+        self.state[487..489].copy_from_slice(&[c1, c2]);
+    }
+
+    ///
+    /// Encrypt or decrypt one block of at most `MAM_SPONGE_RATE` trits
+    ///
+    fn crypt_block(&mut self, input: &[Trit], output: &mut [Trit], last: bool, decrypt: bool) {
+        // control trit
+        let t0 = if input.len() == MAM_SPONGE_RATE { 1 } else { 0 };
+        let t1 = if last { -1 } else { 1 };
+        // Update State
+        self.state[489..492].copy_from_slice(&[t0, t1, -1]);
+        SpongeTransform::transform(&mut self.state);
+
+        for ((out, x), k) in output.iter_mut().zip(input.iter()).zip(self.state.iter()) {
+            let v = if decrypt { x - k } else { x + k };
+            *out = match v {
+                -2 => 1,
+                2 => -1,
+                v => v,
+            };
+        }
+
+        // the plain text is absorbed in both directions
+        let plain_text = if decrypt { &*output } else { input };
+        let mut padr = [0; MAM_SPONGE_RATE + 1];
+        padr[..plain_text.len()].copy_from_slice(plain_text);
+        padr[plain_text.len()] = 1;
+
+        self.state[..MAM_SPONGE_RATE + 1].copy_from_slice(&padr);
+        self.state[MAM_SPONGE_RATE + 1..MAM_SPONGE_RATE + 3].copy_from_slice(&[t1, -1]);
+    }
+}
+
+///
+/// Start a snapshot with its version
+///
+pub(crate) fn new_snapshot() -> Vec<Trit> {
+    let mut snapshot = Vec::new();
+    put_int(&mut snapshot, SNAPSHOT_VERSION, SNAPSHOT_VERSION_LEN);
+    snapshot
+}
+
+///
+/// Close a snapshot with the checksum of its content
+///
+pub(crate) fn seal_snapshot(mut snapshot: Vec<Trit>) -> Vec<Trit> {
+    let mut checksum = [0_i8; MAM_SNAPSHOT_CHECKSUM_SIZE];
+    MamSponge::default()
+        .hash_into(&snapshot, &mut checksum)
+        .unwrap();
+    snapshot.extend_from_slice(&checksum);
+    snapshot
+}
+
+///
+/// Check the version and checksum of a snapshot, returns its content
+///
+pub(crate) fn open_snapshot(trits: &[Trit]) -> Result<&[Trit], String> {
+    if trits.len() < SNAPSHOT_VERSION_LEN + MAM_SNAPSHOT_CHECKSUM_SIZE {
+        return Err("Unexpected end of snapshot".to_owned());
+    }
+    let (content, checksum) = trits.split_at(trits.len() - MAM_SNAPSHOT_CHECKSUM_SIZE);
+    let mut expected = [0_i8; MAM_SNAPSHOT_CHECKSUM_SIZE];
+    MamSponge::default().hash_into(content, &mut expected)?;
+    if checksum != &expected[..] {
+        return Err("Snapshot checksum mismatch".to_owned());
+    }
+
+    let mut input = content;
+    if take_int(&mut input, SNAPSHOT_VERSION_LEN)? != SNAPSHOT_VERSION {
+        return Err("Unsupported snapshot version".to_owned());
+    }
+    Ok(input)
+}
+
+///
+/// Pending input block of a streaming operation
+///
+/// A full block is only processed once more input arrives, as the control
+/// trits of the last block differ from the others.
+///
+#[derive(Clone)]
+struct BlockBuffer {
+    /// Block
+    block: [Trit; MAM_SPONGE_RATE],
+    /// Number of trits in the block
+    len: usize,
+}
 
-        let n: usize = (r_data.len() as f32 / MAM_SPONGE_RATE as f32).ceil() as usize;
-        r_data
-            .chunks(MAM_SPONGE_RATE)
-            .enumerate()
-            .for_each(|(idx, chunk)| {
-                let c0 = if chunk.len() == MAM_SPONGE_RATE { 1 } else { 0 };
-                let c1 = if idx == (n - 1) { -1 } else { 1 };
+impl Default for BlockBuffer {
+    fn default() -> Self {
+        BlockBuffer {
+            block: [0; MAM_SPONGE_RATE],
+            len: 0,
+        }
+    }
+}
 
-                if self.state[MAM_SPONGE_RATE + 1] != 0 {
-                    self.state[489..492].copy_from_slice(&[c0, c1, c2.ctrl()]);
-                    SpongeTransform::transform(&mut self.state);
-                }
+impl BlockBuffer {
+    /// Block is full
+    fn is_full(&self) -> bool {
+        self.len == MAM_SPONGE_RATE
+    }
 
-                let mut padr = [0; MAM_SPONGE_RATE + 1];
-                padr[..chunk.len()].copy_from_slice(&chunk);
-                padr[chunk.len()] = 1;
+    /// Pending trits
+    fn pending(&self) -> &[Trit] {
+        &self.block[..self.len]
+    }
 
-                self.state[..487].copy_from_slice(&padr);
-                self.state[487..489].copy_from_slice(&[c1, c2.ctrl()]);
-            });
+    /// Fill the block from `data` and return what did not fit
+    fn fill<'a>(&mut self, data: &'a [Trit]) -> &'a [Trit] {
+        let n = core::cmp::min(MAM_SPONGE_RATE - self.len, data.len());
+        self.block[self.len..self.len + n].copy_from_slice(&data[..n]);
+        self.len += n;
+        &data[n..]
+    }
+}
 
+///
+/// Streaming Absorber
+///
+/// Absorbs input arriving in pieces, the resulting state is the one of a
+/// single `absorb` of the concatenated input.
+///
+pub struct SpongeAbsorber<'a> {
+    /// Sponge
+    sponge: &'a mut MamSponge,
+    /// Control trit
+    ctrl: Trit,
+    /// Pending block
+    buffer: BlockBuffer,
+}
+
+impl<'a> SpongeAbsorber<'a> {
+    ///
+    /// Absorb the next piece of input
+    ///
+    pub fn update(&mut self, data: &[Trit]) -> Result<(), String> {
+        check_trits(data)?;
+        let mut data = data;
+        while !data.is_empty() {
+            if self.buffer.is_full() {
+                self.sponge
+                    .absorb_block(self.ctrl, self.buffer.pending(), false);
+                self.buffer.len = 0;
+            }
+            data = self.buffer.fill(data);
+        }
         Ok(())
     }
 
-    fn squeeze(&mut self, data: Self::SqueezeInput) -> Vec<Trit> {
-        let n: usize = (data.1 as f32 / MAM_SPONGE_RATE as f32).ceil() as usize;
+    ///
+    /// Absorb the last block
+    ///
+    pub fn finalize(self) {
+        // an empty input is absorbed as a single zero trit
+        let last: &[Trit] = if self.buffer.len == 0 {
+            &[0]
+        } else {
+            self.buffer.pending()
+        };
+        self.sponge.absorb_block(self.ctrl, last, true);
+    }
+}
 
-        (0..data.1)
-            .collect::<Vec<_>>()
-            .chunks(MAM_SPONGE_RATE)
-            .enumerate()
-            .map(|(idx, c_data)| {
-                let mut chunk = vec![0_i8; c_data.len()];
+///
+/// Streaming Encryption / Decryption
+///
+/// Output is returned as soon as a block is known not to be the last one, the
+/// concatenated output is the one of a single `encr` / `decr` call.
+///
+pub struct SpongeCipher<'a> {
+    /// Sponge
+    sponge: &'a mut MamSponge,
+    /// Decrypt instead of encrypt
+    decrypt: bool,
+    /// Pending block
+    buffer: BlockBuffer,
+}
 
-                let t0: Trit = -1;
-                let t1: Trit = if idx == (n - 1) { -1 } else { 1 };
+impl<'a> SpongeCipher<'a> {
+    ///
+    /// Process the next piece of input
+    ///
+    pub fn update(&mut self, data: &[Trit]) -> Result<Vec<Trit>, String> {
+        check_trits(data)?;
+        let mut output = Vec::new();
+        let mut data = data;
+        while !data.is_empty() {
+            if self.buffer.is_full() {
+                self.flush(&mut output, false);
+            }
+            data = self.buffer.fill(data);
+        }
+        Ok(output)
+    }
 
-                self.state[489..492].copy_from_slice(&[t0, t1, data.0.ctrl()]);
+    ///
+    /// Process the last block
+    ///
+    pub fn finalize(mut self) -> Vec<Trit> {
+        let mut output = Vec::new();
+        if self.buffer.len != 0 {
+            self.flush(&mut output, true);
+        }
+        output
+    }
 
-                SpongeTransform::transform(&mut self.state);
+    /// Process the pending block
+    fn flush(&mut self, output: &mut Vec<Trit>, last: bool) {
+        let mut block = [0; MAM_SPONGE_RATE];
+        let len = self.buffer.len;
+        self.sponge
+            .crypt_block(self.buffer.pending(), &mut block[..len], last, self.decrypt);
+        output.extend_from_slice(&block[..len]);
+        self.buffer.len = 0;
+    }
+}
 
-                chunk.copy_from_slice(&self.state[..c_data.len()]);
+///
+/// Streaming Hasher
+///
+/// Hashes input arriving in pieces, the output is the one of `Sponge::hash`
+/// over the concatenated input.
+///
+#[derive(Clone, Default)]
+pub struct MamSpongeHasher {
+    /// Sponge
+    sponge: MamSponge,
+    /// Pending block
+    buffer: BlockBuffer,
+}
 
-                if chunk.len() == MAM_SPONGE_RATE {
-                    self.state[..MAM_SPONGE_RATE].copy_from_slice(&[0; MAM_SPONGE_RATE]);
-                } else {
-                    let mut padr: [Trit; MAM_SPONGE_RATE] = [0; MAM_SPONGE_RATE];
-                    padr[chunk.len() - 1] = 1;
-                    self.state[..MAM_SPONGE_RATE].copy_from_slice(&padr);
-                }
-                self.state[MAM_SPONGE_RATE..489].copy_from_slice(&[t0, t1, data.0.ctrl()]);
-                chunk
-            })
-            .collect::<Vec<_>>()
-            .concat()
+impl MamSpongeHasher {
+    ///
+    /// Hash the next piece of input
+    ///
+    pub fn update(&mut self, data: &[Trit]) -> Result<(), String> {
+        check_trits(data)?;
+        let mut data = data;
+        while !data.is_empty() {
+            if self.buffer.is_full() {
+                self.sponge
+                    .absorb_block(SpongeCtrl::Data.ctrl(), self.buffer.pending(), false);
+                self.buffer.len = 0;
+            }
+            data = self.buffer.fill(data);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Hash value of `hash_len` trits
+    ///
+    pub fn finalize(self, hash_len: usize) -> Vec<Trit> {
+        let mut hash = vec![0_i8; hash_len];
+        self.finalize_into(&mut hash);
+        hash
+    }
+
+    ///
+    /// Hash value into `hash`, its length is the hash length
+    ///
+    pub fn finalize_into(self, hash: &mut [Trit]) {
+        let mut sponge = self.sponge;
+        SpongeAbsorber {
+            sponge: &mut sponge,
+            ctrl: SpongeCtrl::Data.ctrl(),
+            buffer: self.buffer,
+        }
+        .finalize();
+        sponge.squeeze_into(SpongeCtrl::Hash, hash).unwrap();
+    }
+}
+
+impl Sponge for MamSponge {
+    type Error = String;
+    type AbsorbInput = (SpongeCtrl, Vec<Trit>);
+    type SqueezeInput = (SpongeCtrl, usize);
+
+    fn absorb(&mut self, absorb_info: Self::AbsorbInput) -> Result<(), Self::Error> {
+        self.absorb_trits(absorb_info.0, &absorb_info.1)
+    }
+
+    fn squeeze(&mut self, data: Self::SqueezeInput) -> Result<Vec<Trit>, Self::Error> {
+        let mut squeezed = vec![0_i8; data.1];
+        self.squeeze_into(data.0, &mut squeezed)?;
+        Ok(squeezed)
     }
 
     fn hash(&mut self, plain_text: &[Trit], hash_len: usize) -> Result<Vec<Trit>, Self::Error> {
+        let mut hash = vec![0_i8; hash_len];
+        self.hash_into(plain_text, &mut hash)?;
+        Ok(hash)
+    }
+
+    fn hash_into(&mut self, plain_text: &[Trit], hash: &mut [Trit]) -> Result<(), Self::Error> {
         self.reset();
-        self.absorb((SpongeCtrl::Data, plain_text.to_vec()))?;
-        Ok(self.squeeze((SpongeCtrl::Hash, hash_len)))
+        self.absorb_trits(SpongeCtrl::Data, plain_text)?;
+        self.squeeze_into(SpongeCtrl::Hash, hash)
     }
 
-    fn encr(&mut self, plain_text: &[Trit]) -> Vec<Trit> {
+    fn encr(&mut self, plain_text: &[Trit]) -> Result<Vec<Trit>, Self::Error> {
+        check_trits(plain_text)?;
         let mut cipher_text: Vec<Trit> = vec![0_i8; plain_text.len()];
 
-        let n: usize = (plain_text.len() as f32 / MAM_SPONGE_RATE as f32).ceil() as usize;
-        let mut it_pt = plain_text.chunks(MAM_SPONGE_RATE).enumerate();
-        let mut it_ch = cipher_text.chunks_mut(MAM_SPONGE_RATE);
-
-        loop {
-            match it_pt.next() {
-                Some((idx, chunk)) => {
-                    let chunk_ch = it_ch.next().unwrap();
-                    // control trit
-                    let t0 = if chunk.len() == MAM_SPONGE_RATE { 1 } else { 0 };
-                    let t1 = if idx == (n - 1) { -1 } else { 1 };
-                    // Update State
-                    self.state[489..492].copy_from_slice(&[t0, t1, -1]);
-                    SpongeTransform::transform(&mut self.state);
-
-                    for (it, value) in chunk
-                        .iter()
-                        .zip(self.state[..chunk.len()].iter())
-                        .enumerate()
-                    {
-                        chunk_ch[it] = match value.0 + value.1 {
-                            -2 => 1,
-                            2 => -1,
-                            v => v,
-                        };
-                    }
-
-                    let mut padr = [0; MAM_SPONGE_RATE + 1];
-                    padr[..chunk.len()].copy_from_slice(&chunk);
-                    padr[chunk.len()] = 1;
-
-                    self.state[..MAM_SPONGE_RATE + 1].copy_from_slice(&padr);
-                    self.state[MAM_SPONGE_RATE + 1..MAM_SPONGE_RATE + 3].copy_from_slice(&[t1, -1]);
-                }
-                None => break,
-            }
+        let n: usize = plain_text.len().div_ceil(MAM_SPONGE_RATE);
+        let it_pt = plain_text.chunks(MAM_SPONGE_RATE).enumerate();
+        let it_ch = cipher_text.chunks_mut(MAM_SPONGE_RATE);
+
+        for ((idx, chunk), chunk_ch) in it_pt.zip(it_ch) {
+            self.crypt_block(chunk, chunk_ch, idx == n - 1, false);
         }
-        cipher_text
+        Ok(cipher_text)
     }
 
-    fn decr(&mut self, cipher_text: &[Trit]) -> Vec<Trit> {
+    fn decr(&mut self, cipher_text: &[Trit]) -> Result<Vec<Trit>, Self::Error> {
+        check_trits(cipher_text)?;
         let mut plain_text: Vec<Trit> = vec![0_i8; cipher_text.len()];
-        let n: usize = (cipher_text.len() as f32 / MAM_SPONGE_RATE as f32).ceil() as usize;
-        let mut it_pt = plain_text.chunks_mut(MAM_SPONGE_RATE);
-        let mut it_ch = cipher_text.chunks(MAM_SPONGE_RATE).enumerate();
-
-        loop {
-            match it_ch.next() {
-                Some((idx, chunk)) => {
-                    let chunk_pt = it_pt.next().unwrap();
-                    let t0 = if chunk.len() == MAM_SPONGE_RATE { 1 } else { 0 };
-                    let t1 = if idx == (n - 1) { -1 } else { 1 };
-                    // Control
-                    self.state[489..492].copy_from_slice(&[t0, t1, -1]);
-                    SpongeTransform::transform(&mut self.state);
-
-                    for (it, value) in chunk
-                        .iter()
-                        .zip(self.state[..chunk.len()].iter())
-                        .enumerate()
-                    {
-                        chunk_pt[it] = match value.0 - value.1 {
-                            -2 => 1,
-                            2 => -1,
-                            v => v,
-                        };
-                    }
-
-                    let mut padr = [0; MAM_SPONGE_RATE + 1];
-                    padr[..chunk_pt.len()].copy_from_slice(&chunk_pt);
-                    padr[chunk_pt.len()] = 1;
-
-                    self.state[..MAM_SPONGE_RATE + 1].copy_from_slice(&padr);
-                    self.state[MAM_SPONGE_RATE + 1..MAM_SPONGE_RATE + 3].copy_from_slice(&[t1, -1]);
-                }
-                None => break,
-            }
-        }
 
-        plain_text
+        let n: usize = cipher_text.len().div_ceil(MAM_SPONGE_RATE);
+        let it_pt = plain_text.chunks_mut(MAM_SPONGE_RATE);
+        let it_ch = cipher_text.chunks(MAM_SPONGE_RATE).enumerate();
+
+        for ((idx, chunk), chunk_pt) in it_ch.zip(it_pt) {
+            self.crypt_block(chunk, chunk_pt, idx == n - 1, true);
+        }
+        Ok(plain_text)
     }
 
     fn reset(&mut self) {
@@ -290,10 +630,12 @@ impl Sponge for MamSponge {
 #[cfg(test)]
 mod should {
     use crate::{
-        definitions::Sponge,
-        sponge::{MamSponge, SpongeCtrl},
+        definitions::{Sponge, Trit},
+        sponge::{MamSponge, MamSpongeHasher, SpongeCtrl},
     };
+    use alloc::{vec, vec::Vec};
     use iota_conversion::Trinary;
+    use proptest::prelude::*;
     const TRYTES: &str =
         "NOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLM";
 
@@ -301,7 +643,7 @@ mod should {
     fn sponge_absorb_squeeze_data() {
         let mut layer = MamSponge::default();
         layer.absorb((SpongeCtrl::Key, TRYTES.trits())).unwrap();
-        let prn_trits = layer.squeeze((SpongeCtrl::Prn, 81 * 3));
+        let prn_trits = layer.squeeze((SpongeCtrl::Prn, 81 * 3)).unwrap();
         assert!(TRYTES.trits().len() == prn_trits.len())
     }
 
@@ -315,29 +657,151 @@ mod should {
         let k_len = k.len();
         let mut sponge = MamSponge::default();
         sponge.absorb((SpongeCtrl::Key, k.clone())).unwrap();
-        k = sponge.squeeze((SpongeCtrl::Prn, k_len));
+        k = sponge.squeeze((SpongeCtrl::Prn, k_len)).unwrap();
 
         for st in trits_size.iter() {
             let x = vec![0_i8; *st];
 
             sponge.reset();
             sponge.absorb((SpongeCtrl::Key, k.clone())).unwrap();
-            let y = sponge.encr(&x); // Y = E(X)
+            let y = sponge.encr(&x).unwrap(); // Y = E(X)
 
             sponge.reset();
             sponge.absorb((SpongeCtrl::Key, k.clone())).unwrap();
-            let mut z = sponge.decr(&y); // Z = D(E(X))
+            let mut z = sponge.decr(&y).unwrap(); // Z = D(E(X))
             assert_eq!(x, z);
 
             sponge.reset();
             sponge.absorb((SpongeCtrl::Key, k.clone())).unwrap();
-            z = sponge.encr(&z.clone()); // Z = E( Z = X )
+            z = sponge.encr(&z.clone()).unwrap(); // Z = E( Z = X )
             assert_eq!(y, z);
 
             sponge.reset();
             sponge.absorb((SpongeCtrl::Key, k.clone())).unwrap();
-            z = sponge.decr(&z.clone()); //Z = D( Z = E ( X ))
+            z = sponge.decr(&z.clone()).unwrap(); //Z = D( Z = E ( X ))
             assert_eq!(x, z);
         }
     }
+
+    #[test]
+    fn stream_like_one_shot() {
+        let sizes = [0, 1, 242, 485, 486, 487, 972, 1111];
+        let pieces = [1, 5, 486, 500];
+
+        for st in sizes.iter() {
+            let x = TRYTES
+                .trits()
+                .iter()
+                .cycle()
+                .take(*st)
+                .cloned()
+                .collect::<Vec<_>>();
+            let hash = MamSponge::default().hash(&x, 243).unwrap();
+
+            let mut key = MamSponge::default();
+            key.absorb((SpongeCtrl::Key, TRYTES.trits())).unwrap();
+            let y = key.clone().encr(&x).unwrap();
+
+            for piece in pieces.iter() {
+                let mut hasher = MamSpongeHasher::default();
+                x.chunks(*piece)
+                    .for_each(|chunk| hasher.update(chunk).unwrap());
+                assert_eq!(hash, hasher.finalize(243));
+
+                let mut sponge = key.clone();
+                let mut encryptor = sponge.encryptor();
+                let mut z = x
+                    .chunks(*piece)
+                    .flat_map(|chunk| encryptor.update(chunk).unwrap())
+                    .collect::<Vec<_>>();
+                z.extend(encryptor.finalize());
+                assert_eq!(y, z);
+
+                let mut sponge = key.clone();
+                let mut decryptor = sponge.decryptor();
+                let mut z = y
+                    .chunks(*piece)
+                    .flat_map(|chunk| decryptor.update(chunk).unwrap())
+                    .collect::<Vec<_>>();
+                z.extend(decryptor.finalize());
+                assert_eq!(x, z);
+            }
+        }
+    }
+
+    #[test]
+    fn restore_snapshot() {
+        let mut sponge = MamSponge::default();
+        sponge.absorb((SpongeCtrl::Key, TRYTES.trits())).unwrap();
+        let snapshot = sponge.to_trits();
+
+        let mut restored = MamSponge::from_trits(&snapshot).unwrap();
+        assert_eq!(
+            sponge.squeeze((SpongeCtrl::Prn, 243)).unwrap(),
+            restored.squeeze((SpongeCtrl::Prn, 243)).unwrap()
+        );
+
+        let mut corrupted = snapshot.clone();
+        corrupted[100] = if corrupted[100] == 1 { 0 } else { 1 };
+        assert!(MamSponge::from_trits(&corrupted).is_err());
+        assert!(MamSponge::from_trits(&snapshot[1..]).is_err());
+    }
+
+    fn ctrl(idx: usize) -> SpongeCtrl {
+        match idx {
+            0 => SpongeCtrl::Data,
+            1 => SpongeCtrl::Hash,
+            2 => SpongeCtrl::Key,
+            3 => SpongeCtrl::Prn,
+            4 => SpongeCtrl::Text,
+            _ => SpongeCtrl::Mac,
+        }
+    }
+
+    fn trits(max_len: usize) -> impl Strategy<Value = Vec<Trit>> {
+        prop::collection::vec(-1_i8..=1, 0..max_len)
+    }
+
+    proptest! {
+        #[test]
+        fn absorb_checks_control(idx in 0_usize..6, x in trits(1000)) {
+            let valid = matches!(ctrl(idx), SpongeCtrl::Data | SpongeCtrl::Key);
+            let result = MamSponge::default().absorb((ctrl(idx), x));
+            prop_assert_eq!(result.is_ok(), valid);
+        }
+
+        #[test]
+        fn squeeze_checks_control(idx in 0_usize..6, len in 0_usize..1500) {
+            let valid = matches!(ctrl(idx), SpongeCtrl::Hash | SpongeCtrl::Prn | SpongeCtrl::Mac);
+            let result = MamSponge::default().squeeze((ctrl(idx), len));
+            prop_assert_eq!(result.is_ok(), valid);
+            if let Ok(squeezed) = result {
+                prop_assert_eq!(squeezed.len(), len);
+            }
+        }
+
+        #[test]
+        fn decr_any_encr(key in trits(500), x in trits(1500)) {
+            let mut sponge = MamSponge::default();
+            sponge.absorb((SpongeCtrl::Key, key)).unwrap();
+            let y = sponge.clone().encr(&x).unwrap();
+            prop_assert_eq!(y.len(), x.len());
+            prop_assert_eq!(sponge.decr(&y).unwrap(), x);
+        }
+
+        #[test]
+        fn reject_out_of_range_trits(x in prop::collection::vec(-2_i8..=2, 0..20)) {
+            let valid = x.iter().all(|t| (-1..=1).contains(t));
+            let mut sponge = MamSponge::default();
+            prop_assert_eq!(sponge.absorb((SpongeCtrl::Data, x.clone())).is_ok(), valid);
+            prop_assert_eq!(sponge.encr(&x).is_ok(), valid);
+            prop_assert_eq!(sponge.decr(&x).is_ok(), valid);
+            prop_assert_eq!(MamSpongeHasher::default().update(&x).is_ok(), valid);
+        }
+
+        #[test]
+        fn reject_any_malformed_snapshot(snapshot in trits(1000)) {
+            prop_assert!(MamSponge::from_trits(&snapshot).is_err());
+        }
+    }
 }
@@ -1,10 +1,14 @@
 //! MamSpongos Layer
 //!
 use crate::{
-    definitions::{Sponge, Spongos},
-    sponge::{MamSponge, MAM_SPONGE_RATE},
+    constants::{put_int, take_int},
+    definitions::{check_trits, Sponge, Spongos, Trit},
+    sponge::{new_snapshot, open_snapshot, seal_snapshot, MamSponge, MAM_SPONGE_RATE},
 };
-use iota_conversion::Trit;
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+
+/// Length of the position field of a state snapshot
+const SNAPSHOT_POS_LEN: usize = 9;
 
 /// MamSpongos
 #[derive(Debug, Clone)]
@@ -34,41 +38,43 @@ impl Sponge for MamSpongos {
     ///
     /// Proccess input data
     fn absorb(&mut self, trits: Self::AbsorbInput) -> Result<(), Self::Error> {
-        trits.iter().for_each(|trit| {
-            self.sponge.update_state_by_pos(self.pos, trit);
-            self.update();
-        });
-        Ok(())
+        self.absorb_trits(&trits)
     }
 
     /// Squeeze
     ///
     /// Generate output data
-    fn squeeze(&mut self, out_length: Self::SqueezeInput) -> Vec<Trit> {
-        (0..out_length)
-            .map(|_it| {
-                let squeezed_pos = self.sponge.take_state(self.pos);
-                self.sponge.update_state_by_pos(self.pos, &0);
-                self.update();
-
-                squeezed_pos
-            })
-            .collect()
+    fn squeeze(&mut self, out_length: Self::SqueezeInput) -> Result<Vec<Trit>, Self::Error> {
+        let mut squeezed = vec![0_i8; out_length];
+        self.squeeze_into(&mut squeezed);
+        Ok(squeezed)
     }
 
     /// Hash
     ///
     /// Hashing
     fn hash(&mut self, plain_text: &[Trit], hash_len: usize) -> Result<Vec<Trit>, Self::Error> {
+        let mut hash = vec![0_i8; hash_len];
+        self.hash_into(plain_text, &mut hash)?;
+        Ok(hash)
+    }
+
+    /// Hash Into
+    ///
+    /// Hashing into a buffer, without allocating
+    fn hash_into(&mut self, plain_text: &[Trit], hash: &mut [Trit]) -> Result<(), Self::Error> {
         self.reset();
-        self.absorb(plain_text.to_vec())?;
-        Ok(self.squeeze(hash_len))
+        self.absorb_trits(plain_text)?;
+        self.commit();
+        self.squeeze_into(hash);
+        Ok(())
     }
 
     /// Encr
     ///
     /// Encrypt plaintext
-    fn encr(&mut self, plain_text: &[Trit]) -> Vec<Trit> {
+    fn encr(&mut self, plain_text: &[Trit]) -> Result<Vec<Trit>, Self::Error> {
+        check_trits(plain_text)?;
         let mut ciphertext = vec![0_i8; plain_text.len()];
 
         for idx in 0..plain_text.len() {
@@ -81,13 +87,14 @@ impl Sponge for MamSpongos {
             self.update();
         }
 
-        ciphertext
+        Ok(ciphertext)
     }
 
     /// Decr
     ///
     /// Decrypt ciphertext
-    fn decr(&mut self, ciphertext: &[Trit]) -> Vec<Trit> {
+    fn decr(&mut self, ciphertext: &[Trit]) -> Result<Vec<Trit>, Self::Error> {
+        check_trits(ciphertext)?;
         let mut plaintext = vec![0_i8; ciphertext.len()];
 
         for idx in 0..ciphertext.len() {
@@ -97,11 +104,12 @@ impl Sponge for MamSpongos {
                 v => v,
             };
 
-            self.sponge.update_state_by_pos(self.pos, &plaintext[idx]);
+            // the state keeps the cipher text, as in `encr`
+            self.sponge.update_state_by_pos(self.pos, &ciphertext[idx]);
             self.update();
         }
 
-        plaintext
+        Ok(plaintext)
     }
 
     fn reset(&mut self) {
@@ -138,6 +146,55 @@ impl MamSpongos {
         }
     }
 
+    /// Absorb a slice of trits
+    pub fn absorb_trits(&mut self, trits: &[Trit]) -> Result<(), String> {
+        check_trits(trits)?;
+        trits.iter().for_each(|trit| {
+            self.sponge.update_state_by_pos(self.pos, trit);
+            self.update();
+        });
+        Ok(())
+    }
+
+    /// Squeeze into a buffer, its length is the output length
+    pub fn squeeze_into(&mut self, squeezed: &mut [Trit]) {
+        squeezed.iter_mut().for_each(|trit| {
+            *trit = self.sponge.take_state(self.pos);
+            self.sponge.update_state_by_pos(self.pos, &0);
+            self.update();
+        });
+    }
+
+    /// To Trits
+    ///
+    /// Snapshot of the state, including the position in the rate part, so a
+    /// partially absorbed message can be resumed with `from_trits`
+    pub fn to_trits(&self) -> Vec<Trit> {
+        let mut out = new_snapshot();
+        out.extend_from_slice(&self.sponge.state);
+        put_int(&mut out, self.pos as i64, SNAPSHOT_POS_LEN);
+        seal_snapshot(out)
+    }
+
+    /// From Trits
+    ///
+    /// Restore a snapshot produced by `to_trits`
+    pub fn from_trits(trits: &[Trit]) -> Result<Self, String> {
+        let mut input = open_snapshot(trits)?;
+        let sponge = MamSponge::take_snapshot_state(&mut input)?;
+        let pos = take_int(&mut input, SNAPSHOT_POS_LEN)?;
+        if pos < 0 || pos >= MAM_SPONGE_RATE as i64 {
+            return Err("Invalid spongos position".to_owned());
+        }
+        if !input.is_empty() {
+            return Err("Trailing trits after spongos state".to_owned());
+        }
+        Ok(MamSpongos {
+            sponge,
+            pos: pos as usize,
+        })
+    }
+
     /// Reset
     pub fn reset(&mut self) {
         self.sponge = MamSponge::default();
@@ -148,6 +205,7 @@ impl MamSpongos {
 #[cfg(test)]
 mod should {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn spongos_test_encr_decr() {
@@ -158,20 +216,96 @@ mod should {
         let mut spos = MamSpongos::default();
         spos.absorb(x.clone()).unwrap();
         spos.commit();
-        let y = spos.squeeze(FIXED_SIZE);
+        let y = spos.squeeze(FIXED_SIZE).unwrap();
 
         let mut spos1 = MamSpongos::default();
         spos1.absorb(x.clone()).unwrap();
         spos1.commit();
-        let mut z = spos1.encr(&x);
+        let mut z = spos1.encr(&x).unwrap();
 
         assert_eq!(y, z);
 
         spos.reset();
         spos.absorb(x.clone()).unwrap();
         spos.commit();
-        z = spos.decr(&z.clone());
+        z = spos.decr(&z.clone()).unwrap();
 
         assert_eq!(x, z);
     }
+
+    #[test]
+    fn decr_known_answer() {
+        let key = [1, 0, -1].repeat(27);
+        let x = [-1, 0, 1, 1].repeat(7);
+
+        let mut spos = MamSpongos::default();
+        spos.absorb(key.clone()).unwrap();
+        spos.commit();
+        let y = spos.encr(&x).unwrap();
+        spos.commit();
+        let tag = spos.squeeze(9).unwrap();
+        assert_eq!(
+            y,
+            vec![
+                -1, -1, 0, 1, 1, -1, -1, 0, 0, 0, 1, 1, 1, 1, 1, -1, 1, 0, 0, 0, -1, -1, -1, -1, 0,
+                -1, -1, 0
+            ]
+        );
+        assert_eq!(tag, vec![0, -1, 1, 1, 1, 0, -1, 0, 1]);
+
+        let mut spos1 = MamSpongos::default();
+        spos1.absorb(key).unwrap();
+        spos1.commit();
+        assert_eq!(spos1.decr(&y).unwrap(), x);
+        spos1.commit();
+        assert_eq!(spos1.squeeze(9).unwrap(), tag);
+    }
+
+    #[test]
+    fn resume_from_snapshot() {
+        let x = vec![1; 1000];
+
+        let mut spos = MamSpongos::default();
+        spos.absorb(x[..300].to_vec()).unwrap();
+        let mut resumed = MamSpongos::from_trits(&spos.to_trits()).unwrap();
+
+        spos.absorb(x[300..].to_vec()).unwrap();
+        spos.commit();
+        resumed.absorb(x[300..].to_vec()).unwrap();
+        resumed.commit();
+        assert_eq!(spos.squeeze(243).unwrap(), resumed.squeeze(243).unwrap());
+
+        let mut snapshot = spos.to_trits();
+        snapshot.push(0);
+        assert!(MamSpongos::from_trits(&snapshot).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn decr_any_encr(
+            key in prop::collection::vec(-1_i8..=1, 0..1000),
+            x in prop::collection::vec(-1_i8..=1, 0..1000),
+        ) {
+            let mut spos = MamSpongos::default();
+            spos.absorb(key).unwrap();
+            let y = spos.fork().encr(&x).unwrap();
+            prop_assert_eq!(y.len(), x.len());
+            prop_assert_eq!(spos.decr(&y).unwrap(), x);
+        }
+
+        #[test]
+        fn reject_out_of_range_trits(x in prop::collection::vec(-2_i8..=2, 0..20)) {
+            let valid = x.iter().all(|t| (-1..=1).contains(t));
+            let mut spos = MamSpongos::default();
+            prop_assert_eq!(spos.absorb(x.clone()).is_ok(), valid);
+            prop_assert_eq!(spos.encr(&x).is_ok(), valid);
+            prop_assert_eq!(spos.decr(&x).is_ok(), valid);
+        }
+
+        #[test]
+        fn hash_any_length(x in prop::collection::vec(-1_i8..=1, 0..1000), len in 0_usize..1000) {
+            let hash = MamSpongos::default().hash(&x, len).unwrap();
+            prop_assert_eq!(hash.len(), len);
+        }
+    }
 }
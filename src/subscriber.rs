@@ -0,0 +1,714 @@
+//! Subscriber Layer
+//!
+//! Keeps track of the channels and endpoints a subscriber follows, the keys it
+//! holds and the next message it expects from every endpoint. Trust in new
+//! endpoints and channels is only extended through announcements signed by an
+//! already trusted channel.
+//!
+
+use crate::{
+    constants::{put_int, put_sized, take_int, take_sized},
+    definitions::{
+        check_trits,
+        ss::{PrivateKey, PublicKey},
+        Sponge, Trit,
+    },
+    message::{
+        message_digest, MessageIdChain, MAM_CHANNEL_ID_SIZE, MAM_ENDPOINT_ID_SIZE, MAM_MSG_ORD_MAX,
+    },
+    mss::{CachedMssVerifier, MssError, MssPublicKey, MssSignature},
+};
+use alloc::{
+    borrow::ToOwned,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+/// Size of a pre-shared key id
+pub const MAM_PSK_ID_SIZE: usize = 81;
+/// Size of a pre-shared key
+pub const MAM_PSK_KEY_SIZE: usize = 243;
+/// Size of the digest signed by an announcement
+pub const MAM_ANNOUNCEMENT_DIGEST_SIZE: usize = 243;
+
+/// Version of the serialized subscriber state
+const SUBSCRIBER_VERSION: i64 = 2;
+/// Size of the version field
+const VERSION_LEN: usize = 3;
+/// Size of the counters in the serialized state
+const COUNT_LEN: usize = 27;
+
+/// Announcement Kind
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnouncementKind {
+    /// A new endpoint of the announcing channel
+    Endpoint = 0,
+    /// A new channel, announced by the one it replaces
+    Channel = 1,
+}
+
+impl AnnouncementKind {
+    /// Return Trits
+    pub fn trits(self) -> [Trit; 3] {
+        match self {
+            AnnouncementKind::Endpoint => [0, 0, 0],
+            AnnouncementKind::Channel => [1, 0, 0],
+        }
+    }
+}
+
+///
+/// Announcement
+///
+/// Binds an endpoint id or a new channel id to the channel that announced it.
+///
+#[derive(Debug)]
+pub struct Announcement<S> {
+    /// Announcing channel
+    channel_id: Vec<Trit>,
+    /// Kind
+    kind: AnnouncementKind,
+    /// Announced endpoint or channel id
+    announced_id: Vec<Trit>,
+    /// Channel signature over the announcement digest
+    signature: MssSignature<S>,
+}
+
+impl<S> Announcement<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    ///
+    /// Create an announcement from its parts
+    ///
+    pub fn new(
+        channel_id: &[Trit],
+        kind: AnnouncementKind,
+        announced_id: &[Trit],
+        signature: MssSignature<S>,
+    ) -> Self {
+        Announcement {
+            channel_id: channel_id.to_vec(),
+            kind,
+            announced_id: announced_id.to_vec(),
+            signature,
+        }
+    }
+
+    ///
+    /// Sign an announcement with the channel private key
+    ///
+    pub fn sign<K>(
+        private_key: &K,
+        kind: AnnouncementKind,
+        announced_id: &[Trit],
+    ) -> Result<Self, String>
+    where
+        K: PrivateKey<PublicKey = MssPublicKey<S>, Signature = MssSignature<S>, Error = MssError>,
+    {
+        let channel_id = private_key.generate_public_key().to_bytes().to_vec();
+        let digest = Self::digest(&channel_id, kind, announced_id)?;
+        let signature = private_key.sign(&digest).map_err(|e| e.to_string())?;
+
+        Ok(Self::new(&channel_id, kind, announced_id, signature))
+    }
+
+    ///
+    /// Digest signed by the announcing channel
+    ///
+    pub fn digest(
+        channel_id: &[Trit],
+        kind: AnnouncementKind,
+        announced_id: &[Trit],
+    ) -> Result<Vec<Trit>, String> {
+        let mut sponge = S::default();
+        sponge.hash(
+            &[channel_id, &kind.trits()[..], announced_id].concat(),
+            MAM_ANNOUNCEMENT_DIGEST_SIZE,
+        )
+    }
+
+    /// Announcing channel
+    pub fn channel_id(&self) -> &[Trit] {
+        &self.channel_id
+    }
+
+    /// Kind
+    pub fn kind(&self) -> AnnouncementKind {
+        self.kind
+    }
+
+    /// Announced endpoint or channel id
+    pub fn announced_id(&self) -> &[Trit] {
+        &self.announced_id
+    }
+}
+
+///
+/// Followed endpoint
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointEntry {
+    /// Ordinal of the next expected message
+    pub next_ord: i64,
+}
+
+///
+/// Followed channel
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelEntry {
+    /// Ordinal of the next expected message sent on the channel itself
+    pub next_ord: i64,
+    /// Trusted endpoints, by endpoint id
+    pub endpoints: BTreeMap<Vec<Trit>, EndpointEntry>,
+}
+
+///
+/// Subscriber
+///
+/// Channel and endpoint ids are the MSS public keys of the channels and
+/// endpoints, so the id an entry is stored under is also the last verified
+/// key. A new key means a new id, announced by the channel.
+///
+#[derive(Debug, Clone)]
+pub struct Subscriber<S> {
+    /// Followed channels, by channel id
+    channels: BTreeMap<Vec<Trit>, ChannelEntry>,
+    /// Pre-shared keys, by key id
+    psks: BTreeMap<Vec<Trit>, Vec<Trit>>,
+    /// NTRU secret keys
+    ntru_secrets: Vec<Vec<Trit>>,
+    /// Signature verifiers, by MSS public key
+    verifiers: BTreeMap<Vec<Trit>, CachedMssVerifier<S>>,
+    /// Market Data
+    _sponge: PhantomData<S>,
+}
+
+impl<S> Default for Subscriber<S> {
+    fn default() -> Self {
+        Subscriber {
+            channels: BTreeMap::new(),
+            psks: BTreeMap::new(),
+            ntru_secrets: Vec::new(),
+            verifiers: BTreeMap::new(),
+            _sponge: PhantomData,
+        }
+    }
+}
+
+impl<S> Subscriber<S>
+where
+    S: Sponge<Error = String> + Default,
+{
+    ///
+    /// Follow a channel, trusting its id as MSS public key
+    ///
+    pub fn follow(&mut self, channel_id: &[Trit]) -> Result<(), String> {
+        check_trits(channel_id)?;
+        if channel_id.len() != MAM_CHANNEL_ID_SIZE {
+            return Err(format!(
+                "Channel id must be {} trits long",
+                MAM_CHANNEL_ID_SIZE
+            ));
+        }
+        self.channels
+            .entry(channel_id.to_vec())
+            .or_insert_with(|| ChannelEntry {
+                next_ord: 0,
+                endpoints: BTreeMap::new(),
+            });
+        Ok(())
+    }
+
+    ///
+    /// Stop following a channel
+    ///
+    pub fn unfollow(&mut self, channel_id: &[Trit]) -> Option<ChannelEntry> {
+        let channel = self.channels.remove(channel_id)?;
+        self.verifiers.remove(channel_id);
+        for endpoint_id in channel.endpoints.keys() {
+            self.verifiers.remove(endpoint_id);
+        }
+        Some(channel)
+    }
+
+    ///
+    /// Followed channel
+    ///
+    pub fn channel(&self, channel_id: &[Trit]) -> Option<&ChannelEntry> {
+        self.channels.get(channel_id)
+    }
+
+    ///
+    /// Iterate over the followed channels
+    ///
+    pub fn channels(&self) -> impl Iterator<Item = (&Vec<Trit>, &ChannelEntry)> {
+        self.channels.iter()
+    }
+
+    ///
+    /// Check whether `endpoint_id` is trusted on the channel
+    ///
+    /// The channel id is always a trusted endpoint of itself.
+    ///
+    pub fn is_trusted(&self, channel_id: &[Trit], endpoint_id: &[Trit]) -> bool {
+        match self.channels.get(channel_id) {
+            Some(channel) => {
+                channel_id == endpoint_id || channel.endpoints.contains_key(endpoint_id)
+            }
+            None => false,
+        }
+    }
+
+    ///
+    /// Verify an announcement against the trusted channel key and extend trust
+    ///
+    /// Endpoint announcements add the endpoint to the channel, channel
+    /// announcements start following the new channel.
+    ///
+    pub fn process_announcement(&mut self, announcement: &Announcement<S>) -> Result<(), String> {
+        if !self.channels.contains_key(announcement.channel_id()) {
+            return Err("Announcement from a channel that is not followed".to_owned());
+        }
+
+        let digest = Announcement::<S>::digest(
+            announcement.channel_id(),
+            announcement.kind(),
+            announcement.announced_id(),
+        )?;
+        self.verify_signature(announcement.channel_id(), &digest, &announcement.signature)
+            .map_err(|e| format!("Announcement signature rejected: {}", e))?;
+
+        match announcement.kind() {
+            AnnouncementKind::Endpoint => {
+                if announcement.announced_id().len() != MAM_ENDPOINT_ID_SIZE {
+                    return Err(format!(
+                        "Endpoint id must be {} trits long",
+                        MAM_ENDPOINT_ID_SIZE
+                    ));
+                }
+                self.channels
+                    .get_mut(announcement.channel_id())
+                    .unwrap()
+                    .endpoints
+                    .entry(announcement.announced_id().to_vec())
+                    .or_insert(EndpointEntry { next_ord: 0 });
+                Ok(())
+            }
+            AnnouncementKind::Channel => self.follow(announcement.announced_id()),
+        }
+    }
+
+    ///
+    /// Verify a message signed by a trusted endpoint
+    ///
+    /// The endpoint signs `message_digest` of the payload, which binds `ord`.
+    /// Ordinals before the next expected message were already seen and are
+    /// rejected as replays. On success the next expected message moves past
+    /// `ord`.
+    ///
+    pub fn verify_message(
+        &mut self,
+        channel_id: &[Trit],
+        endpoint_id: &[Trit],
+        ord: i64,
+        payload: &[Trit],
+        signature: &MssSignature<S>,
+    ) -> Result<(), String> {
+        if !self.is_trusted(channel_id, endpoint_id) {
+            return Err("Endpoint is not trusted".to_owned());
+        }
+        if !(0..=MAM_MSG_ORD_MAX).contains(&ord) {
+            return Err(format!(
+                "Message ordinal must be in 0..={}",
+                MAM_MSG_ORD_MAX
+            ));
+        }
+        let channel = self.channels.get(channel_id).unwrap();
+        let next_ord = if channel_id == endpoint_id {
+            channel.next_ord
+        } else {
+            channel.endpoints[endpoint_id].next_ord
+        };
+        if ord < next_ord {
+            return Err(format!(
+                "Message {} was already seen, expecting {} or later",
+                ord, next_ord
+            ));
+        }
+
+        let digest = message_digest::<S>(channel_id, endpoint_id, ord, payload)?;
+        self.verify_signature(endpoint_id, &digest, signature)
+            .map_err(|e| format!("Message signature rejected: {}", e))?;
+
+        let channel = self.channels.get_mut(channel_id).unwrap();
+        let next_ord = if channel_id == endpoint_id {
+            &mut channel.next_ord
+        } else {
+            &mut channel.endpoints.get_mut(endpoint_id).unwrap().next_ord
+        };
+        *next_ord = ord
+            .checked_add(1)
+            .ok_or_else(|| "Message ordinal overflow".to_owned())?;
+        Ok(())
+    }
+
+    ///
+    /// Verify a signature with the cached verifier of `public_key`
+    ///
+    /// Consecutive signatures of the same key reuse the authentication path
+    /// nodes verified before.
+    ///
+    fn verify_signature(
+        &mut self,
+        public_key: &[Trit],
+        message: &[Trit],
+        signature: &MssSignature<S>,
+    ) -> Result<(), MssError> {
+        if !self.verifiers.contains_key(public_key) {
+            let verifier = CachedMssVerifier::new(MssPublicKey::from_bytes(public_key)?);
+            self.verifiers.insert(public_key.to_vec(), verifier);
+        }
+        self.verifiers
+            .get_mut(public_key)
+            .unwrap()
+            .verify(message, signature)
+    }
+
+    ///
+    /// Id chain positioned at the next expected message of an endpoint
+    ///
+    pub fn next_messages(
+        &self,
+        channel_id: &[Trit],
+        endpoint_id: &[Trit],
+    ) -> Option<MessageIdChain<S>> {
+        let channel = self.channels.get(channel_id)?;
+        let next_ord = if channel_id == endpoint_id {
+            channel.next_ord
+        } else {
+            channel.endpoints.get(endpoint_id)?.next_ord
+        };
+        Some(MessageIdChain::new(channel_id, endpoint_id, next_ord))
+    }
+
+    ///
+    /// Id of the next expected message of an endpoint
+    ///
+    pub fn next_message_id(
+        &self,
+        channel_id: &[Trit],
+        endpoint_id: &[Trit],
+    ) -> Result<Vec<Trit>, String> {
+        self.next_messages(channel_id, endpoint_id)
+            .ok_or_else(|| "Endpoint is not trusted".to_owned())?
+            .peek_id()
+    }
+
+    ///
+    /// Store a pre-shared key
+    ///
+    pub fn add_psk(&mut self, id: &[Trit], key: &[Trit]) -> Result<(), String> {
+        check_trits(id)?;
+        check_trits(key)?;
+        if id.len() != MAM_PSK_ID_SIZE || key.len() != MAM_PSK_KEY_SIZE {
+            return Err(format!(
+                "PSK id and key must be {} and {} trits long",
+                MAM_PSK_ID_SIZE, MAM_PSK_KEY_SIZE
+            ));
+        }
+        self.psks.insert(id.to_vec(), key.to_vec());
+        Ok(())
+    }
+
+    ///
+    /// Pre-shared key by id
+    ///
+    pub fn psk(&self, id: &[Trit]) -> Option<&[Trit]> {
+        self.psks.get(id).map(|key| &key[..])
+    }
+
+    ///
+    /// Store an NTRU secret key
+    ///
+    pub fn add_ntru_secret(&mut self, secret: &[Trit]) -> Result<(), String> {
+        check_trits(secret)?;
+        self.ntru_secrets.push(secret.to_vec());
+        Ok(())
+    }
+
+    ///
+    /// NTRU secret keys
+    ///
+    pub fn ntru_secrets(&self) -> &[Vec<Trit>] {
+        &self.ntru_secrets
+    }
+
+    ///
+    /// Serialize the registry
+    ///
+    pub fn to_trits(&self) -> Vec<Trit> {
+        let mut out = Vec::new();
+        put_int(&mut out, SUBSCRIBER_VERSION, VERSION_LEN);
+
+        put_int(&mut out, self.channels.len() as i64, COUNT_LEN);
+        for (channel_id, channel) in self.channels.iter() {
+            put_sized(&mut out, channel_id);
+            put_int(&mut out, channel.next_ord, COUNT_LEN);
+            put_int(&mut out, channel.endpoints.len() as i64, COUNT_LEN);
+            for (endpoint_id, endpoint) in channel.endpoints.iter() {
+                put_sized(&mut out, endpoint_id);
+                put_int(&mut out, endpoint.next_ord, COUNT_LEN);
+            }
+        }
+
+        put_int(&mut out, self.psks.len() as i64, COUNT_LEN);
+        for (id, key) in self.psks.iter() {
+            put_sized(&mut out, id);
+            put_sized(&mut out, key);
+        }
+
+        put_int(&mut out, self.ntru_secrets.len() as i64, COUNT_LEN);
+        for secret in self.ntru_secrets.iter() {
+            put_sized(&mut out, secret);
+        }
+        out
+    }
+
+    ///
+    /// Deserialize a registry produced by `to_trits`
+    ///
+    pub fn from_trits(trits: &[Trit]) -> Result<Self, String> {
+        check_trits(trits)?;
+        let mut input = trits;
+        if take_int(&mut input, VERSION_LEN)? != SUBSCRIBER_VERSION {
+            return Err("Unsupported subscriber state version".to_owned());
+        }
+        let mut subscriber = Self::default();
+
+        for _ in 0..take_int(&mut input, COUNT_LEN)? {
+            let channel_id = take_sized(&mut input)?;
+            if channel_id.len() != MAM_CHANNEL_ID_SIZE {
+                return Err(format!(
+                    "Channel id must be {} trits long",
+                    MAM_CHANNEL_ID_SIZE
+                ));
+            }
+            let next_ord = take_next_ord(&mut input)?;
+            let mut endpoints = BTreeMap::new();
+            for _ in 0..take_int(&mut input, COUNT_LEN)? {
+                let endpoint_id = take_sized(&mut input)?;
+                if endpoint_id.len() != MAM_ENDPOINT_ID_SIZE {
+                    return Err(format!(
+                        "Endpoint id must be {} trits long",
+                        MAM_ENDPOINT_ID_SIZE
+                    ));
+                }
+                let next_ord = take_next_ord(&mut input)?;
+                endpoints.insert(endpoint_id, EndpointEntry { next_ord });
+            }
+            subscriber.channels.insert(
+                channel_id,
+                ChannelEntry {
+                    next_ord,
+                    endpoints,
+                },
+            );
+        }
+
+        for _ in 0..take_int(&mut input, COUNT_LEN)? {
+            let id = take_sized(&mut input)?;
+            let key = take_sized(&mut input)?;
+            subscriber.add_psk(&id, &key)?;
+        }
+
+        for _ in 0..take_int(&mut input, COUNT_LEN)? {
+            subscriber.ntru_secrets.push(take_sized(&mut input)?);
+        }
+
+        if !input.is_empty() {
+            return Err("Trailing trits after subscriber state".to_owned());
+        }
+        Ok(subscriber)
+    }
+}
+
+///
+/// Take a serialized next expected ordinal
+///
+/// It is one past the last verified ordinal, so `MAM_MSG_ORD_MAX + 1` once
+/// the last message of an endpoint was verified.
+///
+fn take_next_ord(input: &mut &[Trit]) -> Result<i64, String> {
+    let next_ord = take_int(input, COUNT_LEN)?;
+    if !(0..=MAM_MSG_ORD_MAX + 1).contains(&next_ord) {
+        return Err(format!(
+            "Next message ordinal must be in 0..={}",
+            MAM_MSG_ORD_MAX + 1
+        ));
+    }
+    Ok(next_ord)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{
+        definitions::ss::PrivateKeyGenerator,
+        mss::{MssPrivateKey, MssPrivateKeyGenerator},
+        spongos::MamSpongos,
+        wots::WotsPrivateKeyGenerator,
+    };
+    use iota_conversion::Trinary;
+
+    type Mss = MssPrivateKey<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>>;
+
+    const SEED: &str =
+        "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
+    const ENDPOINT_SEED: &str =
+        "NOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLM";
+
+    fn mss_key(seed: &str) -> Mss {
+        let mss_kg: MssPrivateKeyGenerator<MamSpongos, WotsPrivateKeyGenerator<MamSpongos>> =
+            MssPrivateKeyGenerator::new(2, 1);
+        mss_kg.generate(&seed.trits(), &[0; 18]).unwrap()
+    }
+
+    #[test]
+    fn trust_announced_endpoint() {
+        let channel = mss_key(SEED);
+        let endpoint = mss_key(ENDPOINT_SEED);
+        let chid = channel.generate_public_key().to_bytes().to_vec();
+        let epid = endpoint.generate_public_key().to_bytes().to_vec();
+
+        let mut subscriber: Subscriber<MamSpongos> = Subscriber::default();
+        subscriber.follow(&chid).unwrap();
+        assert!(!subscriber.is_trusted(&chid, &epid));
+
+        let announcement = Announcement::sign(&channel, AnnouncementKind::Endpoint, &epid).unwrap();
+        subscriber.process_announcement(&announcement).unwrap();
+        assert!(subscriber.is_trusted(&chid, &epid));
+
+        let message = SEED.trits();
+        let digest = message_digest::<MamSpongos>(&chid, &epid, 0, &message).unwrap();
+        let signature = endpoint.sign(&digest).unwrap();
+        subscriber
+            .verify_message(&chid, &epid, 0, &message, &signature)
+            .unwrap();
+        assert_eq!(
+            subscriber.next_message_id(&chid, &epid).unwrap(),
+            MessageIdChain::<MamSpongos>::new(&chid, &epid, 1)
+                .peek_id()
+                .unwrap()
+        );
+
+        let digest = message_digest::<MamSpongos>(&chid, &epid, 1, &message).unwrap();
+        let signature = endpoint.sign(&digest).unwrap();
+        subscriber
+            .verify_message(&chid, &epid, 1, &message, &signature)
+            .unwrap();
+        assert_eq!(subscriber.verifiers[&epid].cached_nodes(), 2);
+        subscriber.unfollow(&chid).unwrap();
+        assert!(subscriber.verifiers.is_empty());
+    }
+
+    #[test]
+    fn reject_replayed_ords() {
+        let channel = mss_key(SEED);
+        let chid = channel.generate_public_key().to_bytes().to_vec();
+
+        let mut subscriber: Subscriber<MamSpongos> = Subscriber::default();
+        subscriber.follow(&chid).unwrap();
+
+        let message = SEED.trits();
+        let digest = message_digest::<MamSpongos>(&chid, &chid, 0, &message).unwrap();
+        let signature = channel.sign(&digest).unwrap();
+
+        assert!(subscriber
+            .verify_message(&chid, &chid, 5, &message, &signature)
+            .is_err());
+        assert!(subscriber
+            .verify_message(&chid, &chid, i64::MAX, &message, &signature)
+            .is_err());
+        assert_eq!(subscriber.channel(&chid).unwrap().next_ord, 0);
+
+        subscriber
+            .verify_message(&chid, &chid, 0, &message, &signature)
+            .unwrap();
+        assert_eq!(subscriber.channel(&chid).unwrap().next_ord, 1);
+
+        assert!(subscriber
+            .verify_message(&chid, &chid, 0, &message, &signature)
+            .is_err());
+        assert_eq!(subscriber.channel(&chid).unwrap().next_ord, 1);
+    }
+
+    #[test]
+    fn reject_forged_announcement() {
+        let channel = mss_key(SEED);
+        let forger = mss_key(ENDPOINT_SEED);
+        let chid = channel.generate_public_key().to_bytes().to_vec();
+        let epid = forger.generate_public_key().to_bytes().to_vec();
+
+        let mut subscriber: Subscriber<MamSpongos> = Subscriber::default();
+        subscriber.follow(&chid).unwrap();
+
+        let forged = Announcement::sign(&forger, AnnouncementKind::Endpoint, &epid).unwrap();
+        let forged = Announcement::new(&chid, forged.kind(), &epid, forged.signature);
+
+        assert!(subscriber.process_announcement(&forged).is_err());
+        assert!(!subscriber.is_trusted(&chid, &epid));
+    }
+
+    #[test]
+    fn restore_serialized_state() {
+        let chid = SEED.trits();
+        let epid = ENDPOINT_SEED.trits();
+
+        let mut subscriber: Subscriber<MamSpongos> = Subscriber::default();
+        subscriber.follow(&chid).unwrap();
+        subscriber
+            .channels
+            .get_mut(&chid)
+            .unwrap()
+            .endpoints
+            .insert(epid.clone(), EndpointEntry { next_ord: 7 });
+        subscriber.add_psk(&epid[..81], &chid).unwrap();
+        subscriber.add_ntru_secret(&[1, 0, -1]).unwrap();
+
+        let restored: Subscriber<MamSpongos> =
+            Subscriber::from_trits(&subscriber.to_trits()).unwrap();
+
+        assert_eq!(restored.channel(&chid), subscriber.channel(&chid));
+        assert_eq!(restored.psk(&epid[..81]), Some(&chid[..]));
+        assert_eq!(restored.ntru_secrets(), subscriber.ntru_secrets());
+        assert!(Subscriber::<MamSpongos>::from_trits(&subscriber.to_trits()[1..]).is_err());
+
+        let mut invalid = subscriber.clone();
+        invalid
+            .channels
+            .get_mut(&chid)
+            .unwrap()
+            .endpoints
+            .insert(epid.clone(), EndpointEntry { next_ord: -1 });
+        assert!(Subscriber::<MamSpongos>::from_trits(&invalid.to_trits()).is_err());
+
+        let mut invalid = subscriber.clone();
+        invalid
+            .channels
+            .get_mut(&chid)
+            .unwrap()
+            .endpoints
+            .insert(epid[1..].to_vec(), EndpointEntry { next_ord: 0 });
+        assert!(Subscriber::<MamSpongos>::from_trits(&invalid.to_trits()).is_err());
+
+        let mut invalid = subscriber;
+        invalid.channels.get_mut(&chid).unwrap().next_ord = MAM_MSG_ORD_MAX + 2;
+        assert!(Subscriber::<MamSpongos>::from_trits(&invalid.to_trits()).is_err());
+    }
+}
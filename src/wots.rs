@@ -4,15 +4,17 @@
 //!
 
 use crate::{
-    constants::{mam_divs, mam_mods, trits_get3},
+    constants::long_value,
     definitions::{
+        check_trits, ct_eq,
         ss::{PrivateKey, PrivateKeyGenerator, PublicKey, Signature},
-        Sponge,
+        Sponge, Trit,
     },
     prng::{Prng, PrngDestinationTryte},
+    sponge::MamSponge,
 };
-use iota_conversion::Trit;
-use std::marker::PhantomData;
+use alloc::{string::String, vec, vec::Vec};
+use core::{fmt, marker::PhantomData};
 
 /// Size of a WOTS public key
 pub const MAM_WOTS_PUBLIC_KEY_SIZE: usize = 243;
@@ -22,12 +24,206 @@ pub const MAM_WOTS_PRIVATE_KEY_PART_SIZE: usize = 162;
 pub const MAM_WOTS_PRIVATE_KEY_PART_COUNT: usize = 81;
 /// Size of a WOTS private key
 pub const MAM_WOTS_PRIVATE_KEY_SIZE: usize =
-    (MAM_WOTS_PRIVATE_KEY_PART_SIZE * MAM_WOTS_PRIVATE_KEY_PART_COUNT);
+    MAM_WOTS_PRIVATE_KEY_PART_SIZE * MAM_WOTS_PRIVATE_KEY_PART_COUNT;
+/// Size of a WOTS signature
+pub const MAM_WOTS_SIGNATURE_SIZE: usize = MAM_WOTS_PRIVATE_KEY_SIZE;
+/// Number of message trits covered by a WOTS signature
+pub const MAM_WOTS_MESSAGE_SIZE: usize = 231;
+/// Size of the message digest signed by `sign_message`
+pub const MAM_WOTS_DIGEST_SIZE: usize = 243;
+/// Largest WOTS private key or signature allowed by `WotsParams`
+pub const MAM_WOTS_MAX_PRIVATE_KEY_SIZE: usize = 19683;
+
+///
+/// WOTS Error
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WotsError {
+    /// Public key is not `MAM_WOTS_PUBLIC_KEY_SIZE` trits long
+    InvalidPublicKeySize(usize),
+    /// Signature size does not match the WOTS parameters
+    InvalidSignatureSize(usize),
+    /// Message is shorter than the WOTS parameters require
+    InvalidMessageSize(usize),
+    /// Unsupported WOTS parameters
+    InvalidParams(&'static str),
+    /// Trits outside `-1..=1`
+    InvalidTrits(String),
+    /// Recovered public key does not match
+    PublicKeyMismatch,
+    /// Sponge failure
+    Sponge(String),
+}
+
+impl fmt::Display for WotsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WotsError::InvalidPublicKeySize(len) => write!(
+                f,
+                "WOTS public key must be {} trits long, got {}",
+                MAM_WOTS_PUBLIC_KEY_SIZE, len
+            ),
+            WotsError::InvalidSignatureSize(len) => {
+                write!(f, "Invalid WOTS signature size {}", len)
+            }
+            WotsError::InvalidMessageSize(len) => {
+                write!(f, "WOTS message of {} trits is too short", len)
+            }
+            WotsError::InvalidParams(e) => write!(f, "Invalid WOTS parameters: {}", e),
+            WotsError::InvalidTrits(e) => write!(f, "{}", e),
+            WotsError::PublicKeyMismatch => write!(f, "WOTS public key mismatch"),
+            WotsError::Sponge(e) => write!(f, "Sponge error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WotsError {}
+
+///
+/// WOTS Parameters
+///
+/// The message is cut in `message_digits` digits of `digit_trits` trits, in
+/// base `3^digit_trits`, followed by `checksum_digits` digits of their negated
+/// sum. Every digit selects a position on a hash chain of `81 * security`
+/// trits. Larger digits give shorter signatures but longer chains to walk.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WotsParams {
+    /// Security level, chains are `81 * security` trits wide
+    security: usize,
+    /// Trits per digit
+    digit_trits: usize,
+    /// Number of message digits
+    message_digits: usize,
+    /// Number of checksum digits
+    checksum_digits: usize,
+}
+
+impl WotsParams {
+    /// MAM2 parameters: security 2, base 27, 77 message and 4 checksum digits
+    pub const MAM2: WotsParams = WotsParams {
+        security: 2,
+        digit_trits: 3,
+        message_digits: 77,
+        checksum_digits: 4,
+    };
+
+    ///
+    /// Create WOTS parameters
+    ///
+    /// * `security` - Security level in `1..=3`
+    /// * `digit_trits` - Trits per digit in `1..=4`, the Winternitz base is `3^digit_trits`
+    /// * `message_digits` - Number of message digits signed
+    /// * `checksum_digits` - Number of checksum digits, enough to hold the digit sum
+    ///
+    pub fn new(
+        security: usize,
+        digit_trits: usize,
+        message_digits: usize,
+        checksum_digits: usize,
+    ) -> Result<Self, WotsError> {
+        if !(1..=3).contains(&security) {
+            return Err(WotsError::InvalidParams("security must be in 1..=3"));
+        }
+        if !(1..=4).contains(&digit_trits) {
+            return Err(WotsError::InvalidParams("digits must be 1 to 4 trits long"));
+        }
+        if message_digits == 0 {
+            return Err(WotsError::InvalidParams("no message digit to sign"));
+        }
+
+        let params = WotsParams {
+            security,
+            digit_trits,
+            message_digits,
+            checksum_digits,
+        };
+        if params.private_key_size() > MAM_WOTS_MAX_PRIVATE_KEY_SIZE {
+            return Err(WotsError::InvalidParams(
+                "private key exceeds MAM_WOTS_MAX_PRIVATE_KEY_SIZE",
+            ));
+        }
+        // balanced digits hold (base^n - 1) / 2 at most
+        let capacity = 3_u64
+            .checked_pow((digit_trits * checksum_digits) as u32)
+            .map_or(u64::MAX, |p| (p - 1) / 2);
+        if capacity < (message_digits * params.max_digit()) as u64 {
+            return Err(WotsError::InvalidParams(
+                "checksum digits cannot hold the message digit sum",
+            ));
+        }
+        Ok(params)
+    }
+
+    /// Security level
+    pub fn security(&self) -> usize {
+        self.security
+    }
+
+    /// Trits per digit
+    pub fn digit_trits(&self) -> usize {
+        self.digit_trits
+    }
+
+    /// Winternitz base, `3^digit_trits`
+    pub fn base(&self) -> usize {
+        3_usize.pow(self.digit_trits as u32)
+    }
+
+    /// Number of message digits
+    pub fn message_digits(&self) -> usize {
+        self.message_digits
+    }
+
+    /// Number of checksum digits
+    pub fn checksum_digits(&self) -> usize {
+        self.checksum_digits
+    }
+
+    /// Size of a chain element
+    pub fn part_size(&self) -> usize {
+        81 * self.security
+    }
+
+    /// Number of chains
+    pub fn part_count(&self) -> usize {
+        self.message_digits + self.checksum_digits
+    }
+
+    /// Size of a private key
+    pub fn private_key_size(&self) -> usize {
+        self.part_size() * self.part_count()
+    }
+
+    /// Size of a signature
+    pub fn signature_size(&self) -> usize {
+        self.private_key_size()
+    }
+
+    /// Number of message trits covered by a signature
+    pub fn message_size(&self) -> usize {
+        self.message_digits * self.digit_trits
+    }
+
+    /// Largest balanced digit, a chain is twice as long
+    fn max_digit(&self) -> usize {
+        (self.base() - 1) / 2
+    }
+}
+
+impl Default for WotsParams {
+    fn default() -> Self {
+        WotsParams::MAM2
+    }
+}
 
 /// Wots PrivateKey Generator
 ///
 #[derive(Debug)]
 pub struct WotsPrivateKeyGenerator<S> {
+    /// Parameters
+    params: WotsParams,
     /// Market
     _market: PhantomData<S>,
 }
@@ -40,6 +236,8 @@ pub struct WotsPrivateKeyGenerator<S> {
 pub struct WotsPrivateKey<S> {
     /// Private Key
     state: Vec<Trit>,
+    /// Parameters
+    params: WotsParams,
     /// Market Data
     _sponge: PhantomData<S>,
 }
@@ -64,6 +262,8 @@ pub struct WotsPublicKey<S> {
 pub struct WotsSignature<S> {
     /// Private Key
     state: Vec<Trit>,
+    /// Parameters
+    params: WotsParams,
     /// Market Data
     _sponge: PhantomData<S>,
 }
@@ -73,28 +273,64 @@ impl<S> PrivateKeyGenerator<S> for WotsPrivateKeyGenerator<S> {
     type Error = String;
 
     fn generate(&self, seed: &[Trit], nonce: &[Trit]) -> Result<Self::PrivateKey, Self::Error> {
-        let mut prng = Prng::new(seed);
+        let mut prng = Prng::new(seed)?;
         let state = prng.gen(
             PrngDestinationTryte::DstWotsKey,
             nonce,
-            MAM_WOTS_PRIVATE_KEY_SIZE,
+            self.params.private_key_size(),
         )?;
 
         Ok(WotsPrivateKey {
-            state: state,
+            state,
+            params: self.params,
             _sponge: PhantomData,
         })
     }
 }
 
+impl<S> WotsPrivateKeyGenerator<S> {
+    ///
+    /// Generator of private keys with `params`
+    ///
+    /// MSS keys build their WOTS leaves with the default generator, and the
+    /// MSS signature header has no field for WOTS parameters, so MSS
+    /// signatures always use `WotsParams::MAM2`.
+    ///
+    pub fn with_params(params: WotsParams) -> Self {
+        WotsPrivateKeyGenerator {
+            params,
+            _market: PhantomData,
+        }
+    }
+
+    ///
+    /// Parameters
+    ///
+    pub fn params(&self) -> WotsParams {
+        self.params
+    }
+}
+
+///
+/// Message Digest
+///
+/// Hash of a message of any length, squeezed with the `Hash` control trit.
+/// Parameters covering more than `MAM_WOTS_DIGEST_SIZE` trits get a digest
+/// as long as they cover.
+///
+pub fn message_digest(message: &[Trit], params: &WotsParams) -> Result<Vec<Trit>, WotsError> {
+    check_trits(message).map_err(WotsError::InvalidTrits)?;
+    MamSponge::default()
+        .hash(message, params.message_size().max(MAM_WOTS_DIGEST_SIZE))
+        .map_err(WotsError::Sponge)
+}
+
 impl<S> Default for WotsPrivateKeyGenerator<S>
 where
     S: Default + Sponge<Error = String>,
 {
     fn default() -> Self {
-        return WotsPrivateKeyGenerator {
-            _market: PhantomData,
-        };
+        Self::with_params(WotsParams::MAM2)
     }
 }
 
@@ -104,30 +340,17 @@ where
 {
     type PublicKey = WotsPublicKey<S>;
     type Signature = WotsSignature<S>;
+    type Error = WotsError;
 
     ///
     /// Generate Public Key
     ///
     fn generate_public_key(&self) -> Self::PublicKey {
-        let mut spongos = S::default();
-        let pk_tmp = self
-            .state
-            .chunks(MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-            .map(|chunk| {
-                (0..26)
-                    .map(|_| {
-                        spongos
-                            .hash(&chunk, MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-                            .unwrap()
-                    })
-                    .last()
-                    .unwrap()
-            })
-            .flatten()
-            .collect::<Vec<_>>();
+        let mut state = vec![0_i8; MAM_WOTS_PUBLIC_KEY_SIZE];
+        self.generate_public_key_into(&mut state).unwrap();
 
         WotsPublicKey {
-            state: spongos.hash(&pk_tmp, MAM_WOTS_PUBLIC_KEY_SIZE).unwrap(),
+            state,
             _sponge: PhantomData,
         }
     }
@@ -135,61 +358,70 @@ where
     ///
     /// Sign
     ///
-    fn sign(&self, message: &[i8]) -> Result<Self::Signature, String> {
-        let mut signature = [0_i8; MAM_WOTS_PRIVATE_KEY_SIZE];
-        signature.copy_from_slice(&self.state);
-        let mut spongos = S::default();
-        let mut t = 0;
-        let mut idx = 0;
-
-        for chunk in signature
-            .chunks_mut(MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-            .take(77)
-        {
-            let offset_hash = idx * 3;
-            let mut chk = vec![0; MAM_WOTS_PRIVATE_KEY_PART_SIZE];
-
-            let mut h = trits_get3(&message[offset_hash..offset_hash + 3]);
+    fn sign(&self, message: &[Trit]) -> Result<Self::Signature, Self::Error> {
+        let mut state = vec![0_i8; self.params.signature_size()];
+        self.sign_into(message, &mut state)?;
 
-            t += h as i32;
-            h = h;
+        Ok(WotsSignature {
+            state,
+            params: self.params,
+            _sponge: PhantomData,
+        })
+    }
+}
 
-            for _ in -13..h {
-                chk = spongos
-                    .hash(&chunk, MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-                    .unwrap();
-            }
+impl<S> WotsPrivateKey<S>
+where
+    S: Default + Sponge<Error = String>,
+{
+    ///
+    /// Parameters
+    ///
+    pub fn params(&self) -> WotsParams {
+        self.params
+    }
 
-            chunk.copy_from_slice(&chk);
+    ///
+    /// Sign the digest of `message`, whatever its length
+    ///
+    pub fn sign_message(&self, message: &[Trit]) -> Result<WotsSignature<S>, WotsError> {
+        self.sign(&message_digest(message, &self.params)?)
+    }
 
-            idx += 1;
+    ///
+    /// Generate Public Key into `public_key`, without allocating
+    /// as long as `S::hash_into` does not allocate
+    ///
+    pub fn generate_public_key_into(&self, public_key: &mut [Trit]) -> Result<(), WotsError> {
+        if public_key.len() != MAM_WOTS_PUBLIC_KEY_SIZE {
+            return Err(WotsError::InvalidPublicKeySize(public_key.len()));
         }
 
-        t = -t;
-
-        for chunk in signature
-            .chunks_mut(MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-            .skip(77)
-        {
-            let mut chk = vec![0; MAM_WOTS_PRIVATE_KEY_PART_SIZE];
-            let mut h = mam_mods(t, 19683, 27);
-            t = mam_divs(t, 19683, 27);
+        let mut buffer = [0_i8; MAM_WOTS_MAX_PRIVATE_KEY_SIZE];
+        let pk_tmp = &mut buffer[..self.state.len()];
+        pk_tmp.copy_from_slice(&self.state);
+        let mut spongos = S::default();
 
-            h = h;
+        for chunk in pk_tmp.chunks_mut(self.params.part_size()) {
+            hash_chain(&mut spongos, chunk, 2 * self.params.max_digit())?;
+        }
 
-            for _ in -13..h {
-                chk = spongos
-                    .hash(&chunk, MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-                    .unwrap();
-            }
+        spongos
+            .hash_into(pk_tmp, public_key)
+            .map_err(WotsError::Sponge)
+    }
 
-            chunk.copy_from_slice(&chk);
+    ///
+    /// Sign `message` into `signature`, without allocating
+    /// as long as `S::hash_into` does not allocate
+    ///
+    pub fn sign_into(&self, message: &[Trit], signature: &mut [Trit]) -> Result<(), WotsError> {
+        if signature.len() != self.params.signature_size() {
+            return Err(WotsError::InvalidSignatureSize(signature.len()));
         }
 
-        Ok(WotsSignature {
-            state: signature.to_vec(),
-            _sponge: PhantomData,
-        })
+        signature.copy_from_slice(&self.state);
+        walk_chains::<S>(&self.params, message, signature, 1)
     }
 }
 
@@ -197,6 +429,7 @@ impl<S> Default for WotsPrivateKey<S> {
     fn default() -> Self {
         WotsPrivateKey {
             state: vec![0i8; MAM_WOTS_PRIVATE_KEY_SIZE],
+            params: WotsParams::MAM2,
             _sponge: PhantomData,
         }
     }
@@ -207,31 +440,55 @@ where
     S: Default + Sponge<Error = String>,
 {
     type Signature = WotsSignature<S>;
+    type Error = WotsError;
+    const SIZE: usize = MAM_WOTS_PUBLIC_KEY_SIZE;
 
     ///
     /// Verify
     ///
-    fn verify(&self, message: &[i8], signature: &Self::Signature) -> bool {
-        let public_key = signature.recover_public_key(message);
-        self.state
-            .iter()
-            .zip(public_key.to_bytes().iter())
-            .all(|(st, pk)| st == pk)
+    fn verify(&self, message: &[Trit], signature: &Self::Signature) -> Result<(), Self::Error> {
+        let mut public_key = [0_i8; MAM_WOTS_PUBLIC_KEY_SIZE];
+        signature.recover_public_key_into(message, &mut public_key)?;
+        if !ct_eq(&self.state, &public_key) {
+            return Err(WotsError::PublicKeyMismatch);
+        }
+        Ok(())
     }
     ///
     /// To Bytes
     ///
-    fn to_bytes(&self) -> &[i8] {
+    fn to_bytes(&self) -> &[Trit] {
         &self.state
     }
     ///
-    /// To Bytes
+    /// From Bytes
     ///
-    fn form_bytes(bytes: &[i8]) -> Self {
-        WotsPublicKey {
+    fn from_bytes(bytes: &[Trit]) -> Result<Self, Self::Error> {
+        if bytes.len() != MAM_WOTS_PUBLIC_KEY_SIZE {
+            return Err(WotsError::InvalidPublicKeySize(bytes.len()));
+        }
+        check_trits(bytes).map_err(WotsError::InvalidTrits)?;
+
+        Ok(WotsPublicKey {
             state: bytes.to_vec(),
             _sponge: PhantomData,
-        }
+        })
+    }
+}
+
+impl<S> WotsPublicKey<S>
+where
+    S: Default + Sponge<Error = String>,
+{
+    ///
+    /// Verify a signature made by `sign_message`
+    ///
+    pub fn verify_message(
+        &self,
+        message: &[Trit],
+        signature: &WotsSignature<S>,
+    ) -> Result<(), WotsError> {
+        self.verify(&message_digest(message, &signature.params)?, signature)
     }
 }
 
@@ -240,89 +497,158 @@ where
     S: Default + Sponge<Error = String>,
 {
     type PublicKey = WotsPublicKey<S>;
+    type Error = WotsError;
+    const MAX_SIZE: usize = MAM_WOTS_MAX_PRIVATE_KEY_SIZE;
 
     ///
     /// Recover Public Key
     ///
-    fn recover_public_key(&self, message: &[i8]) -> Self::PublicKey {
-        let mut signature = [0_i8; MAM_WOTS_PRIVATE_KEY_SIZE];
-        signature.copy_from_slice(&self.state);
-        let mut spongos = S::default();
-
-        let mut t = 0;
-        let mut idx = 0;
-
-        for chunk in signature
-            .chunks_mut(MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-            .take(77)
-        {
-            let offset_hash = idx * 3;
-            let mut chk = vec![0; MAM_WOTS_PRIVATE_KEY_PART_SIZE];
-
-            let mut h = trits_get3(&message[offset_hash..offset_hash + 3]);
-
-            t += h as i32;
-            h = -h;
-
-            for _ in -13..h {
-                chk = spongos
-                    .hash(&chunk, MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-                    .unwrap();
-            }
+    fn recover_public_key(&self, message: &[Trit]) -> Result<Self::PublicKey, Self::Error> {
+        let mut state = vec![0_i8; MAM_WOTS_PUBLIC_KEY_SIZE];
+        self.recover_public_key_into(message, &mut state)?;
 
-            chunk.copy_from_slice(&chk);
-
-            idx += 1;
-        }
-
-        t = -t;
-
-        for chunk in signature
-            .chunks_mut(MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-            .skip(77)
-        {
-            let mut chk = vec![0; MAM_WOTS_PRIVATE_KEY_PART_SIZE];
-            let mut h = mam_mods(t, 19683, 27);
-            t = mam_divs(t, 19683, 27);
-
-            h = -h;
-
-            for _ in -13..h {
-                chk = spongos
-                    .hash(&chunk, MAM_WOTS_PRIVATE_KEY_PART_SIZE)
-                    .unwrap();
-            }
-
-            chunk.copy_from_slice(&chk);
-        }
-
-        WotsPublicKey {
-            state: spongos.hash(&signature, MAM_WOTS_PUBLIC_KEY_SIZE).unwrap(),
+        Ok(WotsPublicKey {
+            state,
             _sponge: PhantomData,
-        }
+        })
     }
     ///
     /// To Bytes
     ///
-    fn to_bytes(&self) -> &[i8] {
+    fn to_bytes(&self) -> &[Trit] {
         &self.state
     }
     ///
-    /// To Bytes
+    /// From Bytes
+    ///
+    /// Signatures are decoded with the MAM2 parameters, see `from_bytes_with_params`.
+    ///
+    fn from_bytes(bytes: &[Trit]) -> Result<Self, Self::Error> {
+        Self::from_bytes_with_params(bytes, WotsParams::MAM2)
+    }
+}
+
+impl<S> WotsSignature<S>
+where
+    S: Default + Sponge<Error = String>,
+{
+    ///
+    /// From Bytes, for a signature made with `params`
     ///
-    fn form_bytes(bytes: &[i8]) -> Self {
-        WotsSignature {
+    pub fn from_bytes_with_params(bytes: &[Trit], params: WotsParams) -> Result<Self, WotsError> {
+        if bytes.len() != params.signature_size() {
+            return Err(WotsError::InvalidSignatureSize(bytes.len()));
+        }
+        check_trits(bytes).map_err(WotsError::InvalidTrits)?;
+
+        Ok(WotsSignature {
             state: bytes.to_vec(),
+            params,
             _sponge: PhantomData,
+        })
+    }
+
+    ///
+    /// Parameters
+    ///
+    pub fn params(&self) -> WotsParams {
+        self.params
+    }
+
+    ///
+    /// Recover Public Key into `public_key`, without allocating
+    /// as long as `S::hash_into` does not allocate
+    ///
+    pub fn recover_public_key_into(
+        &self,
+        message: &[Trit],
+        public_key: &mut [Trit],
+    ) -> Result<(), WotsError> {
+        if public_key.len() != MAM_WOTS_PUBLIC_KEY_SIZE {
+            return Err(WotsError::InvalidPublicKeySize(public_key.len()));
         }
+
+        let mut buffer = [0_i8; MAM_WOTS_MAX_PRIVATE_KEY_SIZE];
+        let signature = &mut buffer[..self.state.len()];
+        signature.copy_from_slice(&self.state);
+        walk_chains::<S>(&self.params, message, signature, -1)?;
+
+        S::default()
+            .hash_into(signature, public_key)
+            .map_err(WotsError::Sponge)
     }
 }
 
+///
+/// Hash `chunk` in place `n` times
+///
+fn hash_chain<S>(spongos: &mut S, chunk: &mut [Trit], n: usize) -> Result<(), WotsError>
+where
+    S: Sponge<Error = String>,
+{
+    let mut buffer = [0_i8; 243];
+    let tmp = &mut buffer[..chunk.len()];
+    for _ in 0..n {
+        spongos.hash_into(chunk, tmp).map_err(WotsError::Sponge)?;
+        chunk.copy_from_slice(tmp);
+    }
+    Ok(())
+}
+
+///
+/// Walk every chain of `parts` by `max_digit + sign * h` steps, where `h` is
+/// the message digit (or checksum digit) of the chain
+///
+fn walk_chains<S>(
+    params: &WotsParams,
+    message: &[Trit],
+    parts: &mut [Trit],
+    sign: i32,
+) -> Result<(), WotsError>
+where
+    S: Default + Sponge<Error = String>,
+{
+    let message_size = params.message_size();
+    if message.len() < message_size {
+        return Err(WotsError::InvalidMessageSize(message.len()));
+    }
+    check_trits(&message[..message_size]).map_err(WotsError::InvalidTrits)?;
+
+    let mut spongos = S::default();
+    let base = params.base() as i32;
+    let max_digit = params.max_digit() as i32;
+    let (message_parts, checksum_parts) =
+        parts.split_at_mut(params.message_digits() * params.part_size());
+    let mut t = 0;
+
+    for (digit, chunk) in message[..message_size]
+        .chunks(params.digit_trits())
+        .zip(message_parts.chunks_mut(params.part_size()))
+    {
+        let h = long_value(digit) as i32;
+        t += h;
+
+        hash_chain(&mut spongos, chunk, (max_digit + sign * h) as usize)?;
+    }
+
+    t = -t;
+
+    for chunk in checksum_parts.chunks_mut(params.part_size()) {
+        let h = (t + max_digit).rem_euclid(base) - max_digit;
+        t = (t - h) / base;
+
+        hash_chain(&mut spongos, chunk, (max_digit + sign * h) as usize)?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod should {
     use super::*;
     use crate::spongos::MamSpongos;
     use iota_conversion::Trinary;
+    use proptest::prelude::*;
 
     const SEED: &str =
         "NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNN";
@@ -335,8 +661,223 @@ mod should {
         let private_key: WotsPrivateKey<MamSpongos> = wkg.generate(&seed_trits, &nonce).unwrap();
         let public_key = private_key.generate_public_key();
         let signature = private_key.sign(&seed_trits).unwrap();
-        let _rpk = signature.recover_public_key(&seed_trits);
+        let rpk = signature.recover_public_key(&seed_trits).unwrap();
+
+        assert_eq!(rpk.to_bytes(), public_key.to_bytes());
+        assert!(public_key.verify(&seed_trits, &signature).is_ok());
+        let mut altered = seed_trits.clone();
+        altered[0] = if altered[0] == 1 { 0 } else { 1 };
+        assert_eq!(
+            public_key.verify(&altered, &signature),
+            Err(WotsError::PublicKeyMismatch)
+        );
+    }
 
-        assert_eq!(public_key.verify(&seed_trits, &signature), true);
+    #[test]
+    fn reject_malformed_bytes() {
+        assert_eq!(
+            WotsPublicKey::<MamSpongos>::from_bytes(&[0; 242]).unwrap_err(),
+            WotsError::InvalidPublicKeySize(242)
+        );
+        assert!(WotsPublicKey::<MamSpongos>::from_bytes(&[2; 243]).is_err());
+        assert!(WotsPublicKey::<MamSpongos>::from_bytes(&[1; 243]).is_ok());
+        assert_eq!(
+            WotsSignature::<MamSpongos>::from_bytes(&[0; 13121]).unwrap_err(),
+            WotsError::InvalidSignatureSize(13121)
+        );
+    }
+
+    #[test]
+    fn reject_truncated_input() {
+        let seed_trits = SEED.trits();
+        let wkg: WotsPrivateKeyGenerator<MamSpongos> = WotsPrivateKeyGenerator::default();
+        let private_key = wkg.generate(&seed_trits, &[0; 18]).unwrap();
+        let public_key = private_key.generate_public_key();
+        let signature = private_key.sign(&seed_trits).unwrap();
+
+        let truncated = WotsPublicKey::<MamSpongos> {
+            state: public_key.to_bytes()[..81].to_vec(),
+            _sponge: PhantomData,
+        };
+        assert_eq!(
+            truncated.verify(&seed_trits, &signature),
+            Err(WotsError::PublicKeyMismatch)
+        );
+        assert_eq!(
+            WotsSignature::<MamSpongos>::from_bytes(&signature.to_bytes()[162..]).unwrap_err(),
+            WotsError::InvalidSignatureSize(MAM_WOTS_SIGNATURE_SIZE - 162)
+        );
+        assert!(public_key.verify(&seed_trits, &signature).is_ok());
+    }
+
+    #[test]
+    fn sign_into_buffers() {
+        let seed_trits = SEED.trits();
+        let wkg: WotsPrivateKeyGenerator<MamSpongos> = WotsPrivateKeyGenerator::default();
+        let private_key = wkg.generate(&seed_trits, &[0; 18]).unwrap();
+
+        let mut signature = [0_i8; MAM_WOTS_PRIVATE_KEY_SIZE];
+        private_key.sign_into(&seed_trits, &mut signature).unwrap();
+        let expected = private_key.sign(&seed_trits).unwrap();
+        assert_eq!(&signature[..], expected.to_bytes());
+
+        let mut public_key = [0_i8; MAM_WOTS_PUBLIC_KEY_SIZE];
+        expected
+            .recover_public_key_into(&seed_trits, &mut public_key)
+            .unwrap();
+        assert_eq!(
+            &public_key[..],
+            private_key.generate_public_key().to_bytes()
+        );
+
+        assert_eq!(
+            private_key.sign_into(&seed_trits, &mut [0; 243]),
+            Err(WotsError::InvalidSignatureSize(243))
+        );
+        assert_eq!(
+            private_key.sign_into(&seed_trits[..200], &mut signature),
+            Err(WotsError::InvalidMessageSize(200))
+        );
+
+        let mut message = seed_trits.clone();
+        message[7] = 5;
+        assert!(private_key.sign(&message).is_err());
+        assert!(private_key
+            .generate_public_key()
+            .verify(&message, &expected)
+            .is_err());
+    }
+
+    #[test]
+    fn sign_message_digest() {
+        let wkg: WotsPrivateKeyGenerator<MamSpongos> = WotsPrivateKeyGenerator::default();
+        let private_key = wkg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let public_key = private_key.generate_public_key();
+
+        for message in [vec![], "HI".trits(), SEED.repeat(5).trits()].iter() {
+            let signature = private_key.sign_message(message).unwrap();
+            assert!(public_key.verify_message(message, &signature).is_ok());
+            let digest = message_digest(message, &WotsParams::MAM2).unwrap();
+            assert_eq!(digest.len(), MAM_WOTS_DIGEST_SIZE);
+            assert!(public_key.verify(&digest, &signature).is_ok());
+
+            let mut altered = message.clone();
+            altered.push(1);
+            assert_eq!(
+                public_key.verify_message(&altered, &signature),
+                Err(WotsError::PublicKeyMismatch)
+            );
+        }
+        assert!(private_key.sign_message(&[2]).is_err());
+    }
+
+    #[test]
+    fn keep_mam2_signatures() {
+        let message =
+            "MAMTWOWOTSKNOWNANSWERMESSAGE9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWX"
+                .trits();
+        let wkg: WotsPrivateKeyGenerator<MamSpongos> = WotsPrivateKeyGenerator::default();
+        let private_key = wkg.generate(&SEED.trits(), &[0; 18]).unwrap();
+        let signature = private_key.sign(&message).unwrap();
+        let digest = MamSpongos::default()
+            .hash(signature.to_bytes(), 243)
+            .unwrap();
+
+        assert_eq!(WotsParams::new(2, 3, 77, 4), Ok(WotsParams::MAM2));
+        assert_eq!(
+            private_key.generate_public_key().to_bytes(),
+            &"IOPRQIGVJBUOJBTXAUNPKIR9WIDQDRUYPLRAULKPNIIBGRIYZUPCLDNVRHONV9KULQSYOILDHAYFUAYLW"
+                .trits()[..]
+        );
+        assert_eq!(
+            digest,
+            "KYGIVXGASRDLFEMWXUGITFQOGNDGVYACYGHWVYEOGYHOKBSLIISMASCGKRUXGXGNMNFATYVXUJRTZFOJN"
+                .trits()
+        );
+    }
+
+    #[test]
+    fn sign_with_params() {
+        let message = [SEED.trits(), SEED.trits()].concat();
+        for params in [
+            WotsParams::new(1, 1, 81, 5).unwrap(),
+            WotsParams::new(1, 4, 60, 2).unwrap(),
+            WotsParams::new(3, 2, 60, 3).unwrap(),
+        ]
+        .iter()
+        {
+            let wkg: WotsPrivateKeyGenerator<MamSpongos> =
+                WotsPrivateKeyGenerator::with_params(*params);
+            let private_key = wkg.generate(&SEED.trits(), &[0; 18]).unwrap();
+            let public_key = private_key.generate_public_key();
+            let signature = private_key.sign(&message).unwrap();
+            assert_eq!(signature.to_bytes().len(), params.signature_size());
+            assert!(public_key.verify(&message, &signature).is_ok());
+
+            let mut altered = message.clone();
+            let last = params.message_size() - 1;
+            altered[last] = if altered[last] == 1 { 0 } else { 1 };
+            assert_eq!(
+                public_key.verify(&altered, &signature),
+                Err(WotsError::PublicKeyMismatch)
+            );
+            assert!(WotsSignature::<MamSpongos>::from_bytes(signature.to_bytes()).is_err());
+            let decoded =
+                WotsSignature::<MamSpongos>::from_bytes_with_params(signature.to_bytes(), *params)
+                    .unwrap();
+            assert!(public_key.verify(&message, &decoded).is_ok());
+        }
+    }
+
+    #[test]
+    fn reject_invalid_params() {
+        assert!(WotsParams::new(0, 3, 77, 4).is_err());
+        assert!(WotsParams::new(2, 5, 77, 4).is_err());
+        assert!(WotsParams::new(2, 3, 0, 4).is_err());
+        // 77 digits of at most 13 do not fit in 2 digits of base 27
+        assert!(WotsParams::new(2, 3, 77, 2).is_err());
+        assert!(WotsParams::new(3, 1, 243, 6).is_err());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn check_message_length(
+            seed in prop::collection::vec(-1_i8..=1, 230..250),
+            message in prop::collection::vec(-1_i8..=1, 0..400),
+        ) {
+            let wkg: WotsPrivateKeyGenerator<MamSpongos> = WotsPrivateKeyGenerator::default();
+            let private_key = match wkg.generate(&seed, &[0; 18]) {
+                Ok(private_key) => private_key,
+                Err(_) => {
+                    prop_assert_ne!(seed.len(), 243);
+                    return Ok(());
+                }
+            };
+
+            match private_key.sign(&message) {
+                Ok(signature) => {
+                    prop_assert!(message.len() >= 231);
+                    prop_assert!(private_key.generate_public_key().verify(&message, &signature).is_ok());
+                }
+                Err(_) => prop_assert!(message.len() < 231),
+            }
+        }
+
+        #[test]
+        fn reject_malformed_signature(
+            signature in prop::collection::vec(-1_i8..=1, 0..14000),
+            message in prop::collection::vec(-1_i8..=1, 0..400),
+        ) {
+            let wkg: WotsPrivateKeyGenerator<MamSpongos> = WotsPrivateKeyGenerator::default();
+            let public_key = wkg
+                .generate(&SEED.trits(), &[0; 18])
+                .unwrap()
+                .generate_public_key();
+            if let Ok(signature) = WotsSignature::from_bytes(&signature) {
+                prop_assert!(public_key.verify(&message, &signature).is_err());
+            }
+        }
     }
 }
@@ -0,0 +1,81 @@
+//!
+//! Check that the `_into` WOTS paths do not allocate
+//!
+use mam_rs::{
+    definitions::ss::{PrivateKey, PrivateKeyGenerator},
+    sponge::MamSponge,
+    spongos::MamSpongos,
+    wots::{WotsPrivateKeyGenerator, MAM_WOTS_PUBLIC_KEY_SIZE, MAM_WOTS_SIGNATURE_SIZE},
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+///
+/// Count the allocations made by `f` on the current thread
+///
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn sign_and_recover_without_allocating<S>()
+where
+    S: Default + mam_rs::definitions::Sponge<Error = String>,
+{
+    let seed = [1_i8; 243];
+    let message = [-1_i8; 243];
+    let private_key = WotsPrivateKeyGenerator::<S>::default()
+        .generate(&seed, &[0; 18])
+        .unwrap();
+    let signature = private_key.sign(&message).unwrap();
+
+    let mut signature_buffer = [0_i8; MAM_WOTS_SIGNATURE_SIZE];
+    let mut public_key = [0_i8; MAM_WOTS_PUBLIC_KEY_SIZE];
+    assert_eq!(
+        allocations(|| {
+            private_key
+                .sign_into(&message, &mut signature_buffer)
+                .unwrap();
+            private_key
+                .generate_public_key_into(&mut public_key)
+                .unwrap();
+            signature
+                .recover_public_key_into(&message, &mut public_key)
+                .unwrap();
+        }),
+        0
+    );
+}
+
+#[test]
+fn sign_into_with_mam_sponge() {
+    sign_and_recover_without_allocating::<MamSponge>();
+}
+
+#[test]
+fn sign_into_with_mam_spongos() {
+    sign_and_recover_without_allocating::<MamSpongos>();
+}